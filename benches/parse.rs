@@ -0,0 +1,34 @@
+//! Full-file parse throughput over a generated 10k-line log (`tests/support`).
+//!
+//! `cargo bench --bench parse`, median of 50 samples on a single-core box:
+//!
+//! | change                                        | file_10k_lines |
+//! |-----------------------------------------------|----------------|
+//! | regexes compiled on every line                | 4.77 s         |
+//! | regexes compiled once (`LazyLock` statics)    | 15.7 ms        |
+//! | + fast paths skipping regexes per line kind   | 13.3 ms        |
+//!
+//! With compilation gone, most of the time goes to the watch regex captures,
+//! then to the error strings built by the parsers a line falls through.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rust_anime::parsing::{FileParser, ParserConfig};
+
+#[path = "../tests/support/mod.rs"]
+mod support;
+
+fn parse_file(c: &mut Criterion) {
+    let log = support::generate_log(10_000);
+
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(log.len() as u64));
+    group.bench_function("file_10k_lines", |b| {
+        b.iter(|| FileParser::new(ParserConfig::default()).parse(black_box(&log)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse_file);
+criterion_main!(benches);
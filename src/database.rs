@@ -1,19 +1,137 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
+
 use crate::elements::*;
 use crate::types::*;
 
-trait AnimeDB {
-    fn add_new_anime(&mut self, anime: &str) -> Result<AnimeID, String>;
-    fn add_watch_entry(&mut self, entry: WatchEntry) -> Result<(), String>;
+#[derive(Debug, PartialEq, Clone)]
+pub enum DbError {
+    DuplicateAnime { id: AnimeID, name: String },
+    AnimeNotFound(AnimeID),
+    AnimeOutOfRange(AnimeID),
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DbError::DuplicateAnime { name, .. } => write!(f, "Anime with name {} already exists", name),
+            DbError::AnimeNotFound(anime_id) => write!(f, "Anime ID {} not found", anime_id),
+            DbError::AnimeOutOfRange(anime_id) => write!(f, "Anime ID {} is out of range", anime_id),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+/// Why a database snapshot couldn't be saved or loaded
+#[cfg(feature = "bincode")]
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    /// Not a snapshot file at all
+    BadMagic,
+    /// Written by a different snapshot format version
+    UnsupportedVersion(u16),
+    Encoding(bincode::Error),
+}
+
+#[cfg(feature = "bincode")]
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "Snapshot I/O failed: {}", e),
+            SnapshotError::BadMagic => write!(f, "Not a database snapshot"),
+            SnapshotError::UnsupportedVersion(version) => write!(f, "Unsupported snapshot version {}", version),
+            SnapshotError::Encoding(e) => write!(f, "Invalid snapshot contents: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl std::error::Error for SnapshotError {}
 
-    fn find_anime_by_id<'db>(&'db mut self, anime_id: AnimeID) -> Option<&'db mut Anime>;
-    fn find_anime_by_name<'db>(&'db mut self, name: &str) -> Option<&'db mut Anime>;
+#[cfg(feature = "bincode")]
+impl From<std::io::Error> for SnapshotError {
+    fn from(e: std::io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl From<bincode::Error> for SnapshotError {
+    fn from(e: bincode::Error) -> Self {
+        SnapshotError::Encoding(e)
+    }
+}
+
+pub trait AnimeDB {
+    fn add_new_anime(&mut self, anime: &str) -> Result<AnimeID, DbError>;
+    fn add_watch_entry(&mut self, entry: WatchEntry) -> Result<(), DbError>;
+    fn remove_anime(&mut self, anime_id: AnimeID) -> Result<Anime, DbError>;
+    fn set_status(&mut self, anime_id: AnimeID, status: WatchStatus) -> Result<(), DbError>;
+    /// Timezone the naive entry times were logged in, `None` to keep them naive
+    fn set_timezone(&mut self, timezone: Option<Tz>);
+
+    fn find_anime_by_id(&mut self, anime_id: AnimeID) -> Option<&mut Anime>;
+    fn find_anime_by_name(&mut self, name: &str) -> Option<&mut Anime>;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WatchStatus {
+    #[default]
+    Watching,
+    Completed,
+    Dropped,
+    OnHold,
 }
 
 #[derive(Debug, PartialEq, Clone)]
-struct Anime {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(from = "AnimeFields"))]
+pub struct Anime {
     id: AnimeID,
     name: String,
+    /// Other titles the anime is known by, matched by `search`
+    aliases: Vec<String>,
     watch_entries : Vec<WatchEntry>,
+    /// Positions in `watch_entries` by episode, see `Anime::index_keys` (not serialized, rebuilt by `AnimeFields`)
+    #[cfg_attr(feature = "serde", serde(skip))]
+    episode_index: HashMap<Episode, Vec<usize>>,
+    /// `(start, end, position)` of range entries, kept whole so `1 -> 200000000` isn't one key per episode
+    #[cfg_attr(feature = "serde", serde(skip))]
+    range_index: Vec<(i32, i32, usize)>,
+    total_episodes: Option<u32>,
+    status: WatchStatus,
+}
+
+/// What gets serialized of an `Anime`, so deserializing always goes through `From` and rebuilds the indexes
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct AnimeFields {
+    id: AnimeID,
+    name: String,
+    aliases: Vec<String>,
+    watch_entries: Vec<WatchEntry>,
+    total_episodes: Option<u32>,
+    status: WatchStatus,
+}
+
+#[cfg(feature = "serde")]
+impl From<AnimeFields> for Anime {
+    fn from(fields: AnimeFields) -> Self {
+        let mut anime = Self {
+            aliases: fields.aliases,
+            watch_entries: fields.watch_entries,
+            total_episodes: fields.total_episodes,
+            status: fields.status,
+            ..Self::new(fields.id, fields.name)
+        };
+        anime.reindex_episodes();
+        anime
+    }
 }
 
 impl Anime {
@@ -21,39 +139,808 @@ impl Anime {
         Self {
             id,
             name,
+            aliases: vec![],
             watch_entries: vec![],
+            episode_index: HashMap::new(),
+            range_index: vec![],
+            total_episodes: None,
+            status: WatchStatus::default(),
         }
     }
 
+    /// Episodes an entry is indexed under: each number it covers, or itself when it has none (`7.5`, `OVA`, `S2E05`).
+    /// Ranges go to `range_index` instead.
+    fn index_keys(episode: &Episode) -> Vec<Episode> {
+        match episode.numbers() {
+            numbers if numbers.is_empty() => vec![episode.clone()],
+            numbers => numbers.into_iter().map(Episode::Number).collect(),
+        }
+    }
+
+    fn push_entry(&mut self, entry: WatchEntry) {
+        let position = self.watch_entries.len();
+        match entry.episode {
+            Episode::Range { start, end } => self.range_index.push((start, end, position)),
+            _ => for key in Self::index_keys(&entry.episode) {
+                self.episode_index.entry(key).or_default().push(position);
+            },
+        }
+        self.watch_entries.push(entry);
+    }
+
+    fn reindex_episodes(&mut self) {
+        let entries = std::mem::take(&mut self.watch_entries);
+        self.episode_index.clear();
+        self.range_index.clear();
+        for entry in entries {
+            self.push_entry(entry);
+        }
+    }
+
+    /// Entries covering `episode`, oldest first; a range entry counts for each episode in it
+    pub fn entries_for_episode(&self, episode: &Episode) -> Vec<&WatchEntry> {
+        // Numbered episodes asked for, as intervals
+        let wanted: Vec<(i32, i32)> = match *episode {
+            Episode::Range { start, end } => vec![(start, end)],
+            _ => episode.numbers().into_iter().map(|number| (number, number)).collect(),
+        };
+
+        let mut positions: Vec<usize> = match episode {
+            Episode::Range { .. } => self.episode_index.iter()
+                .filter(|(key, _)| key.number().is_some_and(|number| wanted.iter().any(|&(start, end)| (start..=end).contains(&number))))
+                .flat_map(|(_, positions)| positions)
+                .copied()
+                .collect(),
+            _ => Self::index_keys(episode).iter()
+                .filter_map(|key| self.episode_index.get(key))
+                .flatten()
+                .copied()
+                .collect(),
+        };
+        positions.extend(self.range_index.iter()
+            .filter(|&&(start, end, _)| wanted.iter().any(|&(wanted_start, wanted_end)| start <= wanted_end && wanted_start <= end))
+            .map(|&(_, _, position)| position));
+        positions.sort_unstable();
+        positions.dedup();
+
+        let mut entries: Vec<&WatchEntry> = positions.into_iter().map(|position| &self.watch_entries[position]).collect();
+        entries.sort_by_key(|entry| entry.start_time);
+        entries
+    }
+
+    pub fn id(&self) -> AnimeID {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Entries sorted by start time; `watch_entries` keeps insertion order
+    pub fn entries_chronological(&self) -> Vec<&WatchEntry> {
+        let mut entries: Vec<&WatchEntry> = self.watch_entries().collect();
+        entries.sort_by_key(|entry| entry.start_time);
+        entries
+    }
+
+    /// Consecutive entries, in logged order, where the later one starts earlier on the same day: likely a logging mistake
+    pub fn non_monotonic_entries(&self) -> Vec<(&WatchEntry, &WatchEntry)> {
+        self.watch_entries.windows(2)
+            .filter(|pair| pair[1].start_time < pair[0].start_time && pair[1].start_time.date() == pair[0].start_time.date())
+            .map(|pair| (&pair[0], &pair[1]))
+            .collect()
+    }
+
+    pub fn aliases(&self) -> impl Iterator<Item = &String> {
+        self.aliases.iter()
+    }
+
+    pub fn add_alias(&mut self, alias: &str) {
+        self.aliases.push(alias.to_string());
+    }
+
     pub fn watch_entries(&self) -> impl Iterator<Item = &WatchEntry> {
         self.watch_entries.iter()
     }
+
+    /// Sum of every entry's duration (open-ended entries add nothing)
+    pub fn total_watch_time(&self) -> Duration {
+        self.watch_entries().filter_map(WatchEntry::duration).fold(Duration::zero(), |total, duration| total + duration)
+    }
+
+    /// Time covered by at least one closed entry: unlike `total_watch_time`, overlapping spans count once
+    pub fn effective_watch_time(&self) -> Duration {
+        let mut spans: Vec<(NaiveDateTime, NaiveDateTime)> = self.watch_entries()
+            .filter_map(|entry| entry.end_time.map(|end_time| (entry.start_time, end_time)))
+            .collect();
+        spans.sort();
+
+        let mut total = Duration::zero();
+        let mut current: Option<(NaiveDateTime, NaiveDateTime)> = None;
+        for (start, end) in spans {
+            current = match current {
+                Some((current_start, current_end)) if start <= current_end => Some((current_start, current_end.max(end))),
+                Some((current_start, current_end)) => {
+                    total += current_end - current_start;
+                    Some((start, end))
+                },
+                None => Some((start, end)),
+            };
+        }
+        total + current.map_or_else(Duration::zero, |(start, end)| end - start)
+    }
+
+    /// Mean length of the closed entries, `None` if there are none
+    pub fn average_session_duration(&self) -> Option<Duration> {
+        let durations: Vec<Duration> = self.watch_entries().filter_map(WatchEntry::duration).collect();
+        if durations.is_empty() {
+            return None;
+        }
+
+        let total = durations.iter().fold(Duration::zero(), |total, duration| total + *duration);
+        Some(total / durations.len() as i32)
+    }
+
+    pub fn status(&self) -> WatchStatus {
+        self.status
+    }
+
+    pub fn set_total_episodes(&mut self, total_episodes: Option<u32>) {
+        self.total_episodes = total_episodes;
+    }
+
+    /// Distinct episodes watched; a half episode like `7.5` counts as one
+    pub fn episode_count(&self) -> usize {
+        self.watch_entries()
+            .flat_map(|entry| entry.episode.viewed())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Distinct episodes watched per entry, `None` without entries; open-ended entries still count
+    pub fn avg_episodes_per_session(&self) -> Option<f64> {
+        if self.watch_entries.is_empty() {
+            return None;
+        }
+        Some(self.episode_count() as f64 / self.watch_entries.len() as f64)
+    }
+
+    /// Numbered episodes not watched, up to the total if known or else the last one watched.
+    /// Half episodes are never gaps and don't fill one, and nothing after an open `5 ->` is a gap.
+    pub fn missing_episodes(&self) -> Vec<i32> {
+        let watched: HashSet<i32> = self.watch_entries().flat_map(|entry| entry.episode.numbers()).collect();
+        let last = match self.total_episodes {
+            Some(total) => total as i32,
+            None => watched.iter().copied().max().unwrap_or(0),
+        };
+        let open_from = self.watch_entries()
+            .filter_map(|entry| match entry.episode {
+                Episode::RangeFrom(start) => Some(start),
+                _ => None,
+            })
+            .min()
+            .unwrap_or(i32::MAX);
+
+        (1..=last.min(open_from)).filter(|number| !watched.contains(number)).collect()
+    }
+
+    /// Percentage (0-100) of distinct episodes in 1..=total_episodes that were watched.
+    /// Extras numbered beyond the total are ignored, so it never goes past 100%.
+    /// Only numbered episodes count towards the total, half episodes are extras
+    pub fn completion_percent(&self) -> Option<f32> {
+        let total = self.total_episodes?;
+        if total == 0 {
+            return None;
+        }
+
+        let watched = self.watch_entries()
+            .flat_map(|entry| entry.episode.numbers())
+            .filter(|&number| number >= 1 && number as u32 <= total)
+            .collect::<HashSet<_>>()
+            .len();
+
+        Some(watched as f32 / total as f32 * 100.0)
+    }
+
+    pub fn first_watched(&self) -> Option<NaiveDate> {
+        self.watch_entries().map(|entry| entry.start_time.date()).min()
+    }
+
+    pub fn last_watched(&self) -> Option<NaiveDate> {
+        self.watch_entries().map(|entry| entry.start_time.date()).max()
+    }
+
+    /// Time left to reach `total_episodes` at the pace (episodes per day) of the last `RECENT_PACE_DAYS`
+    /// of activity; `None` without a total or a pace
+    pub fn completion_eta(&self, total_episodes: u32) -> Option<Duration> {
+        const RECENT_PACE_DAYS: u64 = 30;
+
+        if total_episodes == 0 {
+            return None;
+        }
+        let last_watched = self.last_watched()?;
+        let since = last_watched.checked_sub_days(chrono::Days::new(RECENT_PACE_DAYS - 1))?;
+        let recent: Vec<&WatchEntry> = self.watch_entries().filter(|entry| entry.start_time.date() >= since).collect();
+        let episodes: usize = recent.iter().map(|entry| entry.episode.viewed().len()).sum();
+        let first_recent = recent.iter().map(|entry| entry.start_time.date()).min()?;
+        let days = (last_watched - first_recent).num_days() + 1;
+        if episodes == 0 {
+            return None;
+        }
+
+        let watched = self.watch_entries()
+            .flat_map(|entry| entry.episode.numbers())
+            .filter(|&number| number >= 1 && number as u32 <= total_episodes)
+            .collect::<HashSet<_>>()
+            .len();
+        let remaining = total_episodes as usize - watched;
+        let days_left = remaining as f64 * days as f64 / episodes as f64;
+        Some(Duration::seconds((days_left * 86_400.0).round() as i64))
+    }
+}
+
+/// One entry joined with its anime, with plain fields for dataframe and spreadsheet tools
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WatchRecord {
+    pub anime_name: String,
+    pub anime_id: AnimeID,
+    /// Day the entry started on
+    pub date: NaiveDate,
+    pub start: NaiveDateTime,
+    pub end: Option<NaiveDateTime>,
+    pub duration_minutes: Option<i64>,
+    pub episode: String,
+    pub company: Option<String>,
+}
+
+impl WatchRecord {
+    pub fn new(anime: &Anime, entry: &WatchEntry) -> Self {
+        Self {
+            anime_name: anime.name().to_string(),
+            anime_id: anime.id(),
+            date: entry.start_time.date(),
+            start: entry.start_time,
+            end: entry.end_time,
+            duration_minutes: entry.duration().map(|duration| duration.num_minutes()),
+            episode: entry.episode.to_string(),
+            company: entry.company.as_ref().map(ToString::to_string),
+        }
+    }
 }
 
-mod simple_database {  
-    use std::{collections::HashMap};
+pub mod simple_database {  
+    use std::{cell::Cell, collections::{BTreeMap, BTreeSet, HashMap}};
+
+    use chrono::{Datelike, Timelike};
+
+    use crate::report::{Format, NdjsonReport, Report};
 
     use super::*;
 
+    /// How a search query matched an anime, best first
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+    enum SearchRank {
+        Title,
+        Alias,
+        /// Query inside a name or a few typos away, by edit distance
+        Fuzzy(usize),
+    }
+
+    /// Most edits a query can be from a name and still be a fuzzy match
+    const MAX_FUZZY_DISTANCE: usize = 2;
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq)]
+    struct CachedStats {
+        total_watch_time: Duration,
+        entry_count: usize,
+    }
+
+    #[derive(Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct SimpleDatabase {
-        anime_map: HashMap<AnimeID, Anime>
+        anime_map: HashMap<AnimeID, Anime>,
+        next_anime_id: AnimeID,
+        /// `None` when dirty, i.e. an anime was handed out mutably since the last computation
+        #[cfg_attr(feature = "serde", serde(skip))]
+        cached_stats: Cell<Option<CachedStats>>,
+        timezone: Option<Tz>,
+    }
+
+    /// Starts every snapshot file, followed by the little-endian format version
+    #[cfg(feature = "bincode")]
+    const SNAPSHOT_MAGIC: &[u8; 8] = b"RSANIME\0";
+    #[cfg(feature = "bincode")]
+    const SNAPSHOT_VERSION: u16 = 2;
+
+    #[cfg(feature = "bincode")]
+    impl SimpleDatabase {
+        /// Writes a snapshot that `load_bincode` reads back without re-parsing the log
+        pub fn save_bincode(&self, path: impl AsRef<std::path::Path>) -> Result<(), SnapshotError> {
+            let mut bytes = SNAPSHOT_MAGIC.to_vec();
+            bytes.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+            bytes.extend(bincode::serialize(self)?);
+            std::fs::write(path, bytes)?;
+            Ok(())
+        }
+
+        pub fn load_bincode(path: impl AsRef<std::path::Path>) -> Result<Self, SnapshotError> {
+            let bytes = std::fs::read(path)?;
+            let contents = bytes.strip_prefix(SNAPSHOT_MAGIC.as_slice()).ok_or(SnapshotError::BadMagic)?;
+            let (version, contents) = match contents {
+                [low, high, contents @ ..] => (u16::from_le_bytes([*low, *high]), contents),
+                _ => return Err(SnapshotError::BadMagic),
+            };
+            if version != SNAPSHOT_VERSION {
+                return Err(SnapshotError::UnsupportedVersion(version));
+            }
+
+            Ok(bincode::deserialize(contents)?)
+        }
     }
 
     impl SimpleDatabase {
         pub fn new() -> Self {
             Self {
                 anime_map: HashMap::new(),
+                next_anime_id: 0,
+                cached_stats: Cell::new(Some(CachedStats::default())),
+                timezone: None,
+            }
+        }
+
+        /// Timezone the entry times were logged in, used by exports; `None` when they are naive
+        pub fn timezone(&self) -> Option<Tz> {
+            self.timezone
+        }
+
+        /// Total watch time across the library, kept up to date incrementally
+        pub fn cached_total_watch_time(&self) -> Duration {
+            self.cached_stats().total_watch_time
+        }
+
+        pub fn cached_entry_count(&self) -> usize {
+            self.cached_stats().entry_count
+        }
+
+        /// Rebuilds the cached stats from scratch
+        pub fn recompute(&self) {
+            let stats = CachedStats {
+                total_watch_time: self.anime_map.values().map(Anime::total_watch_time).fold(Duration::zero(), |total, time| total + time),
+                entry_count: self.all_watch_entries().count(),
+            };
+            self.cached_stats.set(Some(stats));
+        }
+
+        fn cached_stats(&self) -> CachedStats {
+            if self.cached_stats.get().is_none() {
+                self.recompute();
+            }
+            self.cached_stats.get().unwrap_or_default()
+        }
+
+        fn anime_id_by_name(&self, name: &str) -> Option<AnimeID> {
+            let name = normalize_title(name);
+            self.anime_map.values().find(|anime| normalize_title(&anime.name) == name).map(|anime| anime.id)
+        }
+
+        pub fn animes(&self) -> impl Iterator<Item = &Anime> {
+            self.anime_map.values()
+        }
+
+        fn all_watch_entries(&self) -> impl Iterator<Item = &WatchEntry> {
+            self.anime_map.values().flat_map(|anime| anime.watch_entries())
+        }
+
+        /// The `n` most recently started entries, newest first (ties ordered by anime id)
+        pub fn recent_entries(&self, n: usize) -> Vec<&WatchEntry> {
+            let mut entries: Vec<&WatchEntry> = self.all_watch_entries().collect();
+            entries.sort_by_key(|entry| (std::cmp::Reverse(entry.start_time), entry.anime_id));
+            entries.truncate(n);
+            entries
+        }
+
+        /// Records an entry for `anime_name`, creating the anime unless a normalized name already matches
+        pub fn log(&mut self, anime_name: &str, start_time: NaiveDateTime, end_time: Option<NaiveDateTime>, episode: Episode, company: Option<Company>) -> Result<AnimeID, DbError> {
+            let anime_id = match self.anime_id_by_name(anime_name) {
+                Some(anime_id) => anime_id,
+                None => self.add_new_anime(anime_name)?,
+            };
+            let entry = match end_time {
+                Some(end_time) => WatchEntry::new(anime_id, start_time, end_time, episode, company),
+                None => WatchEntry::open_ended(anime_id, start_time, episode, company),
+            };
+            self.add_watch_entry(entry)?;
+            Ok(anime_id)
+        }
+
+        /// Every entry as a standalone JSON object with its anime name, one per line
+        pub fn to_ndjson(&self) -> String {
+            NdjsonReport.render(self)
+        }
+
+        /// One anime's history rendered by `format`; the log format parses back on its own
+        pub fn export_anime(&self, id: AnimeID, format: Format) -> Result<String, DbError> {
+            let anime = self.anime_map.get(&id).ok_or(DbError::AnimeNotFound(id))?;
+            let single = Self {
+                anime_map: HashMap::from([(id, anime.clone())]),
+                next_anime_id: id + 1,
+                cached_stats: Cell::new(None),
+                timezone: self.timezone,
+            };
+            Ok(format.report().render(&single))
+        }
+
+        /// Merges closed entries of the same anime, episode and session tag whose times touch or overlap,
+        /// e.g. a paused session logged as two lines. The merged entry keeps the tags of both and the
+        /// first rating given. Each anime's entries end up sorted by start.
+        pub fn coalesce_sessions(&mut self) {
+            for anime in self.anime_map.values_mut() {
+                let mut entries = std::mem::take(&mut anime.watch_entries);
+                entries.sort_by_key(|entry| entry.start_time);
+
+                for entry in entries {
+                    let mergeable = anime.watch_entries.iter_mut().rev().find(|previous| {
+                        previous.episode == entry.episode
+                            && previous.session_tag == entry.session_tag
+                            && previous.end_time.zip(entry.end_time).is_some()
+                            && previous.end_time >= Some(entry.start_time)
+                    });
+
+                    match mergeable {
+                        Some(previous) => {
+                            previous.end_time = previous.end_time.max(entry.end_time);
+                            previous.company = match (previous.company.take(), entry.company) {
+                                (Some(mut company), Some(other)) => {
+                                    company.merge(&other);
+                                    Some(company)
+                                },
+                                (company, other) => company.or(other),
+                            };
+                            for tag in entry.tags {
+                                if !previous.tags.contains(&tag) {
+                                    previous.tags.push(tag);
+                                }
+                            }
+                            previous.rating = previous.rating.or(entry.rating);
+                        },
+                        None => anime.watch_entries.push(entry),
+                    }
+                }
+                anime.reindex_episodes();
+            }
+
+            self.cached_stats.set(None);
+        }
+
+        /// Animes matching `query` by title, then by alias, then fuzzily (ties ordered by id)
+        pub fn search(&self, query: &str) -> Vec<&Anime> {
+            let query = normalize_title(query);
+            if query.is_empty() {
+                return vec![];
+            }
+
+            let rank = |anime: &Anime| {
+                if normalize_title(&anime.name) == query {
+                    return Some(SearchRank::Title);
+                }
+                let names: Vec<String> = std::iter::once(&anime.name).chain(&anime.aliases).map(|name| normalize_title(name)).collect();
+                if names[1..].contains(&query) {
+                    return Some(SearchRank::Alias);
+                }
+                names.iter()
+                    .map(|name| if name.contains(&query) { 0 } else { edit_distance(name, &query) })
+                    .min()
+                    .filter(|distance| *distance <= MAX_FUZZY_DISTANCE)
+                    .map(SearchRank::Fuzzy)
+            };
+
+            let mut results: Vec<(SearchRank, &Anime)> = self.anime_map.values()
+                .filter_map(|anime| rank(anime).map(|rank| (rank, anime)))
+                .collect();
+            results.sort_by_key(|(rank, anime)| (*rank, anime.id));
+            results.into_iter().map(|(_, anime)| anime).collect()
+        }
+
+        /// Entries of every anime in start order, split wherever the time between one entry's end
+        /// (its start if open-ended) and the next one's start exceeds `max_gap`
+        pub fn binge_sessions(&self, max_gap: Duration) -> Vec<Vec<&WatchEntry>> {
+            let mut entries: Vec<&WatchEntry> = self.all_watch_entries().collect();
+            entries.sort_by_key(|entry| (entry.start_time, entry.anime_id));
+
+            let mut sessions: Vec<Vec<&WatchEntry>> = vec![];
+            for entry in entries {
+                match sessions.last_mut() {
+                    Some(session) if session.last().is_some_and(|last| {
+                        entry.start_time - last.end_time.unwrap_or(last.start_time) <= max_gap
+                    }) => session.push(entry),
+                    _ => sessions.push(vec![entry]),
+                }
+            }
+            sessions
+        }
+
+        /// Later copies of entries repeating an earlier one's anime, times and episode
+        pub fn find_duplicate_entries(&self) -> Vec<&WatchEntry> {
+            let mut animes: Vec<&Anime> = self.anime_map.values().collect();
+            animes.sort_by_key(|anime| anime.id);
+
+            animes.into_iter()
+                .flat_map(|anime| {
+                    anime.watch_entries.iter().enumerate()
+                        .filter(|(idx, entry)| anime.watch_entries[..*idx].iter().any(|earlier| earlier.same_session(entry)))
+                        .map(|(_, entry)| entry)
+                })
+                .collect()
+        }
+
+        /// Total time of closed entries watched with `name` in company
+        pub fn time_with(&self, name: &str) -> Duration {
+            self.all_watch_entries()
+                .filter(|entry| entry.company.as_ref().is_some_and(|company| company.contains(name)))
+                .filter_map(WatchEntry::duration)
+                .fold(Duration::zero(), |total, duration| total + duration)
+        }
+
+        /// Closed entry with the longest duration (midnight-crossing ones included), earliest start on ties
+        pub fn longest_session(&self) -> Option<&WatchEntry> {
+            self.all_watch_entries()
+                .filter_map(|entry| entry.duration().map(|duration| (duration, entry)))
+                .min_by_key(|(duration, entry)| (std::cmp::Reverse(*duration), entry.start_time, entry.anime_id))
+                .map(|(_, entry)| entry)
+        }
+
+        /// Every entry as a `WatchRecord`, ordered by anime id and then start time
+        pub fn flat_records(&self) -> Vec<WatchRecord> {
+            let mut animes: Vec<&Anime> = self.animes().collect();
+            animes.sort_by_key(|anime| anime.id());
+
+            animes.into_iter()
+                .flat_map(|anime| anime.entries_chronological().into_iter().map(move |entry| WatchRecord::new(anime, entry)))
+                .collect()
+        }
+
+        /// Anime with the most entries; ties go to the longest total watch time, then the lowest id
+        pub fn most_watched_anime(&self) -> Option<&Anime> {
+            self.animes()
+                .min_by_key(|anime| (std::cmp::Reverse(anime.watch_entries().count()), std::cmp::Reverse(anime.total_watch_time()), anime.id()))
+        }
+
+        /// Distinct calendar dates on which any entry started
+        pub fn active_days(&self) -> usize {
+            self.all_watch_entries().map(|entry| entry.start_time.date()).collect::<HashSet<_>>().len()
+        }
+
+        /// Every named co-watcher across all entries, normalized (anonymous `+N` members are not counted)
+        pub fn distinct_companions(&self) -> BTreeSet<String> {
+            self.all_watch_entries()
+                .filter_map(|entry| entry.company.as_ref())
+                .flat_map(|company| company.names().iter().map(|name| normalize_title(name)))
+                .collect()
+        }
+
+        /// Mean rating over every rated entry, `None` if none is rated
+        pub fn overall_average_rating(&self) -> Option<f32> {
+            let ratings: Vec<u8> = self.all_watch_entries().filter_map(|entry| entry.rating).collect();
+            if ratings.is_empty() {
+                return None;
+            }
+            Some(ratings.iter().map(|&rating| rating as f32).sum::<f32>() / ratings.len() as f32)
+        }
+
+        /// Number of rated entries per rating, for a histogram
+        pub fn rating_distribution(&self) -> BTreeMap<u8, usize> {
+            let mut distribution = BTreeMap::new();
+            for rating in self.all_watch_entries().filter_map(|entry| entry.rating) {
+                *distribution.entry(rating).or_default() += 1;
+            }
+            distribution
+        }
+
+        /// Entries grouped by the exact set of named co-watchers (normalized as in `distinct_companions`),
+        /// oldest first; entries watched alone or without company recorded share the empty set
+        pub fn watch_parties(&self) -> Vec<(BTreeSet<String>, Vec<&WatchEntry>)> {
+            let mut parties: BTreeMap<BTreeSet<String>, Vec<&WatchEntry>> = BTreeMap::new();
+            for entry in self.all_watch_entries() {
+                let companions = entry.company.as_ref()
+                    .map(|company| company.names().iter().map(|name| normalize_title(name)).collect())
+                    .unwrap_or_default();
+                parties.entry(companions).or_default().push(entry);
+            }
+
+            parties.into_iter()
+                .map(|(companions, mut entries)| {
+                    entries.sort_by_key(|entry| (entry.start_time, entry.anime_id));
+                    (companions, entries)
+                })
+                .collect()
+        }
+
+        /// Entries started between `from` and `to` (both inclusive), oldest first (ties ordered by anime id)
+        pub fn entries_in_range(&self, from: NaiveDate, to: NaiveDate) -> Vec<&WatchEntry> {
+            let mut entries: Vec<&WatchEntry> = self.all_watch_entries()
+                .filter(|entry| (from..=to).contains(&entry.start_time.date()))
+                .collect();
+            entries.sort_by_key(|entry| (entry.start_time, entry.anime_id));
+            entries
+        }
+
+        /// Entries started on `month`/`day` of any year, oldest first (Feb 29 only matches leap years)
+        pub fn on_this_day(&self, month: u32, day: u32) -> Vec<&WatchEntry> {
+            let mut entries: Vec<&WatchEntry> = self.all_watch_entries()
+                .filter(|entry| (entry.start_time.month(), entry.start_time.day()) == (month, day))
+                .collect();
+            entries.sort_by_key(|entry| (entry.start_time, entry.anime_id));
+            entries
+        }
+
+        /// Animes watched on `date`, each with that day's entries in chronological order
+        pub fn day_breakdown(&self, date: NaiveDate) -> Vec<(&Anime, Vec<&WatchEntry>)> {
+            let mut breakdown: Vec<(&Anime, Vec<&WatchEntry>)> = self.anime_map.values()
+                .map(|anime| {
+                    let mut entries: Vec<&WatchEntry> = anime.watch_entries()
+                        .filter(|entry| entry.start_time.date() == date)
+                        .collect();
+                    entries.sort_by_key(|entry| entry.start_time);
+                    (anime, entries)
+                })
+                .filter(|(_, entries)| !entries.is_empty())
+                .collect();
+
+            breakdown.sort_by_key(|(anime, _)| anime.id);
+            breakdown
+        }
+
+        /// Animes with an entry started in `month` of `year`, most watched that month first (ties ordered by id)
+        pub fn animes_in_month(&self, year: i32, month: u32) -> Vec<&Anime> {
+            let in_month = |entry: &&WatchEntry| (entry.start_time.year(), entry.start_time.month()) == (year, month);
+            let mut animes: Vec<(Duration, &Anime)> = self.anime_map.values()
+                .filter(|anime| anime.watch_entries().any(|entry| in_month(&entry)))
+                .map(|anime| {
+                    let time = anime.watch_entries()
+                        .filter(in_month)
+                        .filter_map(WatchEntry::duration)
+                        .fold(Duration::zero(), |total, duration| total + duration);
+                    (time, anime)
+                })
+                .collect();
+
+            animes.sort_by_key(|(time, anime)| (std::cmp::Reverse(*time), anime.id));
+            animes.into_iter().map(|(_, anime)| anime).collect()
+        }
+
+        /// Checks that every anime is stored under its own id and that its entries point back to it,
+        /// listing every inconsistency found
+        pub fn verify_integrity(&self) -> Result<(), Vec<String>> {
+            let mut problems = vec![];
+            let mut anime_ids: Vec<&AnimeID> = self.anime_map.keys().collect();
+            anime_ids.sort();
+            for anime_id in anime_ids {
+                let anime = &self.anime_map[anime_id];
+                if anime.id != *anime_id {
+                    problems.push(format!("Anime \"{}\" has id {} but is stored under id {}", anime.name, anime.id, anime_id));
+                }
+                for entry in anime.watch_entries().filter(|entry| entry.anime_id != *anime_id) {
+                    problems.push(format!("Entry at {} of anime {} (\"{}\") points to anime {}", entry.start_time, anime_id, anime.name, entry.anime_id));
+                }
+            }
+
+            if problems.is_empty() {
+                Ok(())
+            } else {
+                Err(problems)
+            }
+        }
+
+        /// Animes currently marked `status`, ordered by id
+        pub fn animes_with_status(&self, status: WatchStatus) -> Vec<&Anime> {
+            let mut animes: Vec<&Anime> = self.anime_map.values().filter(|anime| anime.status == status).collect();
+            animes.sort_by_key(|anime| anime.id);
+            animes
+        }
+
+        /// Animes in `schedule` (latest aired episode per anime) whose last numbered episode watched is
+        /// behind it, with how many episodes behind, most behind first
+        pub fn behind_by(&self, schedule: &HashMap<AnimeID, i32>) -> Vec<(&Anime, i32)> {
+            let mut behind: Vec<(&Anime, i32)> = schedule.iter()
+                .filter_map(|(anime_id, &latest_aired)| {
+                    let anime = self.anime_map.get(anime_id)?;
+                    let last_watched = anime.watch_entries().flat_map(|entry| entry.episode.numbers()).max().unwrap_or(0);
+                    Some((anime, latest_aired - last_watched))
+                })
+                .filter(|(_, behind)| *behind > 0)
+                .collect();
+            behind.sort_by_key(|(anime, behind)| (std::cmp::Reverse(*behind), anime.id));
+            behind
+        }
+
+        /// Animes being watched (status `Watching`) with an entry in the 30 days up to `as_of`,
+        /// most recently watched first
+        pub fn continue_watching(&self, as_of: NaiveDate) -> Vec<&Anime> {
+            const RECENT_DAYS: i64 = 30;
+
+            let mut watching: Vec<(NaiveDate, &Anime)> = self.anime_map.values()
+                .filter(|anime| anime.status == WatchStatus::Watching)
+                .filter_map(|anime| {
+                    let last_watched = anime.watch_entries().map(|entry| entry.start_time.date()).filter(|&date| date <= as_of).max()?;
+                    ((as_of - last_watched).num_days() <= RECENT_DAYS).then_some((last_watched, anime))
+                })
+                .collect();
+
+            watching.sort_by_key(|(last_watched, anime)| (std::cmp::Reverse(*last_watched), anime.id));
+            watching.into_iter().map(|(_, anime)| anime).collect()
+        }
+
+        /// Unfinished animes last watched more than `threshold_days` before `as_of`
+        pub fn stalled_animes(&self, as_of: NaiveDate, threshold_days: i64) -> Vec<&Anime> {
+            let mut stalled: Vec<&Anime> = self.anime_map.values()
+                .filter(|anime| match anime.last_watched() {
+                    Some(last_watched) => (as_of - last_watched).num_days() > threshold_days,
+                    None => false,
+                })
+                .filter(|anime| anime.completion_percent().is_none_or(|percent| percent < 100.0))
+                .collect();
+
+            stalled.sort_by_key(|anime| anime.id);
+            stalled
+        }
+
+        /// Distinct (anime, episode) pairs watched; rewatches count once and ranges count each episode
+        pub fn unique_episodes_watched(&self) -> usize {
+            self.all_watch_entries()
+                .flat_map(|entry| entry.episode.viewed().into_iter().map(move |episode| (entry.anime_id, episode)))
+                .collect::<HashSet<_>>()
+                .len()
+        }
+
+        /// Summed duration of the entries matching `pred` (open-ended entries add nothing)
+        pub fn watch_time_where<F: Fn(&WatchEntry) -> bool>(&self, pred: F) -> Duration {
+            self.all_watch_entries()
+                .filter(|entry| pred(entry))
+                .filter_map(WatchEntry::duration)
+                .fold(Duration::zero(), |total, duration| total + duration)
+        }
+
+        /// Watch time per weekday (Monday first), counted on the day each entry started
+        pub fn watch_time_by_weekday(&self) -> [Duration; 7] {
+            let mut by_weekday = [Duration::zero(); 7];
+            for entry in self.all_watch_entries() {
+                if let Some(duration) = entry.duration() {
+                    by_weekday[entry.start_time.weekday().num_days_from_monday() as usize] += duration;
+                }
+            }
+            by_weekday
+        }
+
+        /// Watch time per month (January first) of `year`, counted on the month each entry started
+        pub fn monthly_watch_time(&self, year: i32) -> [Duration; 12] {
+            let mut by_month = [Duration::zero(); 12];
+            for entry in self.all_watch_entries().filter(|entry| entry.start_time.year() == year) {
+                if let Some(duration) = entry.duration() {
+                    by_month[entry.start_time.month0() as usize] += duration;
+                }
+            }
+            by_month
+        }
+
+        /// Number of entries started at each hour of the day
+        pub fn start_hour_histogram(&self) -> [usize; 24] {
+            let mut histogram = [0; 24];
+            for entry in self.all_watch_entries() {
+                histogram[entry.start_time.hour() as usize] += 1;
             }
+            histogram
         }
     }
 
     impl AnimeDB for SimpleDatabase {
-        fn add_new_anime(&mut self, title: &str) -> Result<AnimeID, String> {
+        fn add_new_anime(&mut self, title: &str) -> Result<AnimeID, DbError> {
             
-            match self.find_anime_by_name(title) {
-                Some(_) => Err(format!("Anime with name {} already exists", title)),
+            match self.anime_id_by_name(title) {
+                Some(id) => Err(DbError::DuplicateAnime { id, name: title.to_string() }),
                 None => {
-                    let anime_id = self.anime_map.len();
+                    let anime_id = self.next_anime_id;
+                    self.next_anime_id += 1;
                     let anime = Anime::new(anime_id, title.to_string());
                     self.anime_map.insert(anime_id, anime);
                     Ok(anime_id)
@@ -62,26 +949,61 @@ mod simple_database {
 
         }
 
-        fn add_watch_entry(&mut self, entry: WatchEntry) -> Result<(), String> {
+        fn add_watch_entry(&mut self, entry: WatchEntry) -> Result<(), DbError> {
             let anime_id = entry.anime_id;
-            if anime_id >= self.anime_map.len() {
-                return Err(format!("Anime ID {} is out of range", anime_id));
+            if anime_id >= self.next_anime_id {
+                return Err(DbError::AnimeOutOfRange(anime_id));
             }
             
             let anime = 
-                self.find_anime_by_id(anime_id)
-                .ok_or_else(|| format!("Anime ID {} not found", anime_id))?;
+                self.anime_map.get_mut(&anime_id)
+                .ok_or(DbError::AnimeNotFound(anime_id))?;
+
+            if let Some(mut stats) = self.cached_stats.get() {
+                stats.total_watch_time += entry.duration().unwrap_or_else(Duration::zero);
+                stats.entry_count += 1;
+                self.cached_stats.set(Some(stats));
+            }
+
+            anime.push_entry(entry);
+            // Watching an anime's last missing episode completes it
+            if anime.status == WatchStatus::Watching && anime.completion_percent() == Some(100.0) {
+                anime.status = WatchStatus::Completed;
+            }
+            Ok(())
+        }
+
+        fn remove_anime(&mut self, anime_id: AnimeID) -> Result<Anime, DbError> {
+            let anime = self.anime_map.remove(&anime_id).ok_or(DbError::AnimeNotFound(anime_id))?;
+
+            if let Some(mut stats) = self.cached_stats.get() {
+                stats.total_watch_time -= anime.total_watch_time();
+                stats.entry_count -= anime.watch_entries.len();
+                self.cached_stats.set(Some(stats));
+            }
+
+            Ok(anime)
+        }
 
-            anime.watch_entries.push(entry);
+        fn set_status(&mut self, anime_id: AnimeID, status: WatchStatus) -> Result<(), DbError> {
+            let anime = self.anime_map.get_mut(&anime_id).ok_or(DbError::AnimeNotFound(anime_id))?;
+            anime.status = status;
             Ok(())
         }
 
+        fn set_timezone(&mut self, timezone: Option<Tz>) {
+            self.timezone = timezone;
+        }
+
         fn find_anime_by_id(&mut self, anime_id: AnimeID) -> Option<&mut Anime> {
+            // The caller may change the entries behind our back
+            self.cached_stats.set(None);
             self.anime_map.get_mut(&anime_id)
         }
 
         fn find_anime_by_name(&mut self, name: &str) -> Option<&mut Anime> {
-            self.anime_map.values_mut().find(|anime| anime.name == name)
+            let anime_id = self.anime_id_by_name(name)?;
+            self.find_anime_by_id(anime_id)
         }
 
 
@@ -90,7 +1012,7 @@ mod simple_database {
 
 #[cfg(test)]
 mod tests {
-    use chrono::NaiveDateTime;
+    use chrono::{DateTime, NaiveDate, NaiveDateTime};
 
     use super::*;
 
@@ -114,8 +1036,12 @@ mod tests {
     fn add_existing_anime_fails() {
         let mut db = simple_database::SimpleDatabase::new();
 
-        let anime_id = db.add_new_anime("My Anime");
-        assert!(db.add_new_anime("My Anime").is_err(),"Adding existing anime should fail, but was successful");
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        assert_eq!(
+            db.add_new_anime("My Anime"),
+            Err(DbError::DuplicateAnime { id: anime_id, name: "My Anime".to_string() }),
+            "Adding existing anime should fail, but was successful"
+        );
     }
 
     #[test]
@@ -158,8 +1084,8 @@ mod tests {
         
         let entry = WatchEntry::new(
             anime_id,
-            NaiveDateTime::from_timestamp(0, 0), 
-            NaiveDateTime::from_timestamp(1, 0),
+            DateTime::from_timestamp(0, 0).unwrap().naive_utc(), 
+            DateTime::from_timestamp(1, 0).unwrap().naive_utc(),
             Episode::from("1").unwrap(),
             None,
         );
@@ -182,24 +1108,24 @@ mod tests {
         
         let entry_1 = WatchEntry::new(
             anime_id,
-            NaiveDateTime::from_timestamp(0, 0), 
-            NaiveDateTime::from_timestamp(1, 0),
+            DateTime::from_timestamp(0, 0).unwrap().naive_utc(), 
+            DateTime::from_timestamp(1, 0).unwrap().naive_utc(),
             Episode::from("1").unwrap(),
             None,
         );
 
         let entry_2 = WatchEntry::new(
             anime_id,
-            NaiveDateTime::from_timestamp(0, 0), 
-            NaiveDateTime::from_timestamp(1, 0),
+            DateTime::from_timestamp(0, 0).unwrap().naive_utc(), 
+            DateTime::from_timestamp(1, 0).unwrap().naive_utc(),
             Episode::from("2").unwrap(),
             None,
         );
 
         let entry_3 = WatchEntry::new(
             anime_id,
-            NaiveDateTime::from_timestamp(0, 0), 
-            NaiveDateTime::from_timestamp(1, 0),
+            DateTime::from_timestamp(0, 0).unwrap().naive_utc(), 
+            DateTime::from_timestamp(1, 0).unwrap().naive_utc(),
             Episode::from("3").unwrap(),
             None,
         );
@@ -224,16 +1150,16 @@ mod tests {
 
         let entry_1 = WatchEntry::new(
             anime_id_1,
-            NaiveDateTime::from_timestamp(0, 0), 
-            NaiveDateTime::from_timestamp(1, 0),
+            DateTime::from_timestamp(0, 0).unwrap().naive_utc(), 
+            DateTime::from_timestamp(1, 0).unwrap().naive_utc(),
             Episode::from("1").unwrap(),
             None,
         );
 
         let entry_2 = WatchEntry::new(
             anime_id_2,
-            NaiveDateTime::from_timestamp(0, 0), 
-            NaiveDateTime::from_timestamp(1, 0),
+            DateTime::from_timestamp(0, 0).unwrap().naive_utc(), 
+            DateTime::from_timestamp(1, 0).unwrap().naive_utc(),
             Episode::from("2").unwrap(),
             None,
         );
@@ -249,4 +1175,1050 @@ mod tests {
         assert_eq!(anime_2.watch_entries.len(), 1);
         assert_eq!(anime_2.watch_entries[0], entry_2);
     }
-}
\ No newline at end of file
+
+    fn add_episode_entries(db: &mut simple_database::SimpleDatabase, anime_id: AnimeID, episodes: &[&str]) {
+        for episode in episodes {
+            let entry = WatchEntry::new(
+                anime_id,
+                DateTime::from_timestamp(0, 0).unwrap().naive_utc(),
+                DateTime::from_timestamp(1, 0).unwrap().naive_utc(),
+                Episode::from(episode).unwrap(),
+                None,
+            );
+            db.add_watch_entry(entry).unwrap();
+        }
+    }
+
+    #[test]
+    fn avg_episodes_per_session_counts_ranges() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        assert_eq!(db.find_anime_by_id(anime_id).unwrap().avg_episodes_per_session(), None);
+        add_episode_entries(&mut db, anime_id, &["1", "2 -> 4"]);
+        db.add_watch_entry(WatchEntry::open_ended(anime_id, DateTime::from_timestamp(60, 0).unwrap().naive_utc(), Episode::Number(5), None)).unwrap();
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        assert_eq!(anime.avg_episodes_per_session(), Some(5.0 / 3.0));
+    }
+
+    #[test]
+    fn completion_eta_from_recent_pace() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        assert_eq!(db.find_anime_by_id(anime_id).unwrap().completion_eta(16), None);
+
+        // Long before the recent window, so it doesn't count towards the pace
+        db.add_watch_entry(entry_at(anime_id, "01/01/2022 20:00", "01/01/2022 20:30", "1")).unwrap();
+        for (day, episodes) in [("17", "2 -> 3"), ("18", "4 -> 5"), ("19", "6 -> 7")] {
+            let date = format!("{}/03/2022", day);
+            db.add_watch_entry(entry_at(anime_id, &format!("{} 20:00", date), &format!("{} 21:00", date), episodes)).unwrap();
+        }
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        // 2 episodes a day, 16 - 7 = 9 left
+        assert_eq!(anime.completion_eta(16), Some(chrono::Duration::hours(108)));
+        assert_eq!(anime.completion_eta(17), Some(chrono::Duration::days(5)));
+        assert_eq!(anime.completion_eta(5), Some(chrono::Duration::zero()));
+        assert_eq!(anime.completion_eta(0), None);
+    }
+
+    #[test]
+    fn completion_percent_partial() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        add_episode_entries(&mut db, anime_id, &["1", "2", "2", "3"]);
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        anime.set_total_episodes(Some(12));
+        assert_eq!(anime.completion_percent(), Some(25.0));
+    }
+
+    #[test]
+    fn completion_percent_full_ignores_extras() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        add_episode_entries(&mut db, anime_id, &["1", "2", "3", "4", "5"]);
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        anime.set_total_episodes(Some(4));
+        assert_eq!(anime.completion_percent(), Some(100.0));
+    }
+
+    #[test]
+    fn completion_percent_unknown_total() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        add_episode_entries(&mut db, anime_id, &["1", "2"]);
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        assert_eq!(anime.completion_percent(), None);
+    }
+
+    #[test]
+    fn entries_chronological_keeps_insertion_order() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        db.add_watch_entry(entry_at(anime_id, "20/03/2022 20:00", "20/03/2022 20:30", "2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "21/03/2022 20:00", "21/03/2022 20:30", "3")).unwrap();
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        let episodes = |entries: Vec<&WatchEntry>| entries.iter().map(|entry| entry.episode.number().unwrap()).collect::<Vec<_>>();
+        assert_eq!(episodes(anime.entries_chronological()), vec![1, 2, 3]);
+        assert_eq!(episodes(anime.watch_entries().collect()), vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn effective_watch_time_counts_overlaps_once() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 20:20", "19/03/2022 20:45", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 20:25", "19/03/2022 20:28", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 21:00", "19/03/2022 21:10", "2")).unwrap();
+        db.add_watch_entry(WatchEntry::open_ended(anime_id, NaiveDateTime::parse_from_str("19/03/2022 22:00", "%d/%m/%Y %H:%M").unwrap(), Episode::Number(3), None)).unwrap();
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        assert_eq!(anime.total_watch_time(), Duration::minutes(30 + 25 + 3 + 10));
+        assert_eq!(anime.effective_watch_time(), Duration::minutes(45 + 10));
+    }
+
+    #[test]
+    fn average_session_duration_skips_open_entries() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        assert_eq!(db.find_anime_by_id(anime_id).unwrap().average_session_duration(), None);
+
+        let start = NaiveDateTime::parse_from_str("19/03/2022 23:00", "%d/%m/%Y %H:%M").unwrap();
+        db.add_watch_entry(WatchEntry::open_ended(anime_id, start, Episode::Number(1), None)).unwrap();
+        assert_eq!(db.find_anime_by_id(anime_id).unwrap().average_session_duration(), None);
+
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 20:20", "2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 20:30", "19/03/2022 21:00", "3")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 21:00", "19/03/2022 21:41", "4")).unwrap();
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        assert_eq!(anime.average_session_duration(), Some(chrono::Duration::seconds((20 + 30 + 41) * 60 / 3)));
+    }
+
+    fn entry_at(anime_id: AnimeID, start: &str, end: &str, episode: &str) -> WatchEntry {
+        WatchEntry::new(
+            anime_id,
+            NaiveDateTime::parse_from_str(start, "%d/%m/%Y %H:%M").unwrap(),
+            NaiveDateTime::parse_from_str(end, "%d/%m/%Y %H:%M").unwrap(),
+            Episode::from(episode).unwrap(),
+            None,
+        )
+    }
+
+    #[test]
+    fn non_monotonic_entries_within_a_day() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let clean = db.add_new_anime("Clean").unwrap();
+        db.add_watch_entry(entry_at(clean, "19/03/2022 23:30", "19/03/2022 23:55", "1")).unwrap();
+        db.add_watch_entry(entry_at(clean, "20/03/2022 00:05", "20/03/2022 00:30", "2")).unwrap();
+        db.add_watch_entry(entry_at(clean, "20/03/2022 20:00", "20/03/2022 20:25", "3")).unwrap();
+        assert!(db.find_anime_by_id(clean).unwrap().non_monotonic_entries().is_empty());
+
+        let scrambled = db.add_new_anime("Scrambled").unwrap();
+        db.add_watch_entry(entry_at(scrambled, "19/03/2022 21:00", "19/03/2022 21:25", "1")).unwrap();
+        db.add_watch_entry(entry_at(scrambled, "19/03/2022 20:00", "19/03/2022 20:25", "2")).unwrap();
+        db.add_watch_entry(entry_at(scrambled, "19/03/2022 22:00", "19/03/2022 22:25", "3")).unwrap();
+        db.add_watch_entry(entry_at(scrambled, "18/03/2022 22:00", "18/03/2022 22:25", "4")).unwrap();
+
+        let anime = db.find_anime_by_id(scrambled).unwrap();
+        let pairs: Vec<(String, String)> = anime.non_monotonic_entries().into_iter()
+            .map(|(earlier, later)| (earlier.episode.to_string(), later.episode.to_string()))
+            .collect();
+        assert_eq!(pairs, vec![("01".to_string(), "02".to_string())]);
+    }
+
+    #[test]
+    fn day_breakdown_groups_by_anime() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+        let anime_id_3 = db.add_new_anime("My Anime 3").unwrap();
+
+        let entry_1b = entry_at(anime_id_1, "19/03/2022 21:00", "19/03/2022 21:30", "2");
+        let entry_1a = entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1");
+        let entry_2 = entry_at(anime_id_2, "19/03/2022 22:00", "19/03/2022 22:30", "5");
+        let other_day = entry_at(anime_id_1, "20/03/2022 20:00", "20/03/2022 20:30", "3");
+        let only_other_day = entry_at(anime_id_3, "20/03/2022 10:00", "20/03/2022 10:30", "1");
+
+        for entry in [&entry_1b, &entry_1a, &entry_2, &other_day, &only_other_day] {
+            db.add_watch_entry(entry.clone()).unwrap();
+        }
+
+        let date = NaiveDate::parse_from_str("19/03/2022", "%d/%m/%Y").unwrap();
+        let breakdown = db.day_breakdown(date);
+
+        assert_eq!(breakdown.len(), 2);
+        assert_eq!(breakdown[0].0.id, anime_id_1);
+        assert_eq!(breakdown[0].1, vec![&entry_1a, &entry_1b]);
+        assert_eq!(breakdown[1].0.id, anime_id_2);
+        assert_eq!(breakdown[1].1, vec![&entry_2]);
+    }
+
+    #[test]
+    fn first_and_last_watched() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        let empty_id = db.add_new_anime("Empty Anime").unwrap();
+
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 20:30", "2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "02/01/2022 20:00", "02/01/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "10/02/2022 23:40", "11/02/2022 00:20", "3")).unwrap();
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        assert_eq!(anime.first_watched(), Some(NaiveDate::parse_from_str("02/01/2022", "%d/%m/%Y").unwrap()));
+        assert_eq!(anime.last_watched(), Some(NaiveDate::parse_from_str("19/03/2022", "%d/%m/%Y").unwrap()));
+
+        let empty = db.find_anime_by_id(empty_id).unwrap();
+        assert_eq!(empty.first_watched(), None);
+        assert_eq!(empty.last_watched(), None);
+    }
+
+    #[test]
+    fn recent_entries_newest_first() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+
+        db.add_watch_entry(entry_at(anime_id_2, "19/03/2022 21:00", "19/03/2022 21:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "18/03/2022 20:00", "18/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "20/03/2022 20:00", "20/03/2022 20:30", "2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "20/03/2022 20:00", "20/03/2022 20:30", "2")).unwrap();
+
+        let recent: Vec<(AnimeID, i32)> = db.recent_entries(3).iter()
+            .map(|entry| (entry.anime_id, entry.episode.number().unwrap()))
+            .collect();
+        assert_eq!(recent, vec![(anime_id_1, 2), (anime_id_2, 2), (anime_id_2, 1)]);
+
+        assert_eq!(db.recent_entries(10).len(), 4);
+        assert!(db.recent_entries(0).is_empty());
+    }
+
+    #[test]
+    fn export_anime_only_includes_that_anime() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "19/03/2022 20:30", "19/03/2022 21:00", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "20/03/2022 20:00", "20/03/2022 20:30", "2")).unwrap();
+
+        let log = db.export_anime(anime_id_1, crate::report::Format::Log).unwrap();
+        assert_eq!(log, "19/03/2022\nMy Anime 1:\n20:00 - 20:30 01\n\n20/03/2022\nMy Anime 1:\n20:00 - 20:30 02\n");
+        assert!(!db.export_anime(anime_id_1, crate::report::Format::Csv).unwrap().contains("My Anime 2"));
+
+        let mut exported = simple_database::SimpleDatabase::new();
+        crate::parsing::FileParser::new(crate::parsing::ParserConfig::default()).parse_into(&log, &mut exported).unwrap();
+        assert_eq!(exported.animes().count(), 1);
+        assert_eq!(exported.cached_entry_count(), 2);
+
+        assert_eq!(db.export_anime(7, crate::report::Format::Json), Err(DbError::AnimeNotFound(7)));
+    }
+
+    #[test]
+    fn distinct_companions_across_entries() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+
+        let with_company = |mut entry: WatchEntry, company: &str| {
+            entry.company = Some(Company::from_str(company).unwrap());
+            entry
+        };
+        db.add_watch_entry(with_company(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1"), "{Gary, Amim}")).unwrap();
+        db.add_watch_entry(with_company(entry_at(anime_id_1, "20/03/2022 20:00", "20/03/2022 20:30", "2"), "{gary,+2}")).unwrap();
+        db.add_watch_entry(with_company(entry_at(anime_id_2, "20/03/2022 21:00", "20/03/2022 21:30", "1"), "{Lucas Romero, AMIM}")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "21/03/2022 21:00", "21/03/2022 21:30", "2")).unwrap();
+
+        let companions: Vec<String> = db.distinct_companions().into_iter().collect();
+        assert_eq!(companions, vec!["amim", "gary", "lucas romero"]);
+    }
+
+    #[test]
+    fn ratings_average_and_distribution() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        assert_eq!(db.overall_average_rating(), None);
+
+        let rated = |mut entry: WatchEntry, rating: Option<u8>| {
+            entry.rating = rating;
+            entry
+        };
+        db.add_watch_entry(rated(entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 20:30", "1"), Some(8))).unwrap();
+        db.add_watch_entry(rated(entry_at(anime_id, "19/03/2022 20:30", "19/03/2022 21:00", "2"), None)).unwrap();
+        db.add_watch_entry(rated(entry_at(anime_id, "19/03/2022 21:00", "19/03/2022 21:30", "3"), Some(6))).unwrap();
+        db.add_watch_entry(rated(entry_at(anime_id, "19/03/2022 21:30", "19/03/2022 22:00", "4"), Some(8))).unwrap();
+
+        assert_eq!(db.overall_average_rating(), Some(22.0 / 3.0));
+        assert_eq!(db.rating_distribution(), std::collections::BTreeMap::from([(6, 1), (8, 2)]));
+    }
+
+    #[test]
+    fn behind_by_latest_aired() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let behind = db.add_new_anime("Behind").unwrap();
+        let caught_up = db.add_new_anime("Caught Up").unwrap();
+        let not_airing = db.add_new_anime("Not Airing").unwrap();
+        add_episode_entries(&mut db, behind, &["1 -> 4"]);
+        add_episode_entries(&mut db, caught_up, &["1", "2", "3"]);
+        add_episode_entries(&mut db, not_airing, &["1"]);
+
+        let schedule = HashMap::from([(behind, 7), (caught_up, 3), (42, 10)]);
+        let result: Vec<(AnimeID, i32)> = db.behind_by(&schedule).into_iter().map(|(anime, behind)| (anime.id(), behind)).collect();
+        assert_eq!(result, vec![(behind, 3)]);
+    }
+
+    #[test]
+    fn watch_parties_group_by_exact_companions() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+
+        let with_company = |mut entry: WatchEntry, company: &str| {
+            entry.company = Some(Company::from_str(company).unwrap());
+            entry
+        };
+        db.add_watch_entry(with_company(entry_at(anime_id_2, "20/03/2022 21:00", "20/03/2022 21:30", "1"), "{Amim, gary}")).unwrap();
+        db.add_watch_entry(with_company(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1"), "{Gary, Amim}")).unwrap();
+        db.add_watch_entry(with_company(entry_at(anime_id_1, "20/03/2022 20:00", "20/03/2022 20:30", "2"), "{Gary}")).unwrap();
+        db.add_watch_entry(with_company(entry_at(anime_id_1, "21/03/2022 20:00", "21/03/2022 20:30", "3"), "{solo}")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "21/03/2022 21:00", "21/03/2022 21:30", "2")).unwrap();
+
+        // Each party as its companions and "anime id/episode" of its entries
+        let parties: Vec<(String, Vec<String>)> = db.watch_parties().into_iter()
+            .map(|(companions, entries)| (
+                companions.into_iter().collect::<Vec<_>>().join(","),
+                entries.into_iter().map(|entry| format!("{}/{}", entry.anime_id, entry.episode)).collect(),
+            ))
+            .collect();
+        let party = |companions: &str, entries: &[String]| (companions.to_string(), entries.to_vec());
+        assert_eq!(parties, vec![
+            party("", &[format!("{}/03", anime_id_1), format!("{}/02", anime_id_2)]),
+            party("amim,gary", &[format!("{}/01", anime_id_1), format!("{}/01", anime_id_2)]),
+            party("gary", &[format!("{}/02", anime_id_1)]),
+        ]);
+    }
+
+    #[test]
+    fn solo_entries_are_kept_apart_from_unrecorded_company() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+
+        let mut solo = entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 20:30", "1");
+        solo.company = Some(Company::solo());
+        db.add_watch_entry(solo).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 20:30", "19/03/2022 21:00", "2")).unwrap();
+
+        assert_eq!(db.time_with("solo"), Duration::zero());
+        assert!(db.distinct_companions().is_empty());
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        let companies: Vec<Option<bool>> = anime.watch_entries().map(|entry| entry.company.as_ref().map(Company::is_solo)).collect();
+        assert_eq!(companies, vec![Some(true), None]);
+    }
+
+    #[test]
+    fn log_finds_or_creates_anime_by_name() {
+        let mut db = simple_database::SimpleDatabase::new();
+        let time = |time: &str| NaiveDateTime::parse_from_str(time, "%d/%m/%Y %H:%M").unwrap();
+
+        let other_id = db.add_new_anime("Erased").unwrap();
+        let anime_id = db.log("One Pace: Wano", time("19/03/2022 20:00"), Some(time("19/03/2022 20:30")), Episode::Number(1), None).unwrap();
+        let same_id = db.log("one pace:  wano", time("19/03/2022 20:30"), None, Episode::Number(2), Some(Company::solo())).unwrap();
+        assert_eq!(same_id, anime_id);
+        assert_ne!(anime_id, other_id);
+
+        assert_eq!(db.animes().count(), 2);
+        assert_eq!(db.cached_entry_count(), 2);
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        assert_eq!(anime.name(), "One Pace: Wano");
+        assert_eq!(anime.episode_count(), 2);
+        assert_eq!(anime.watch_entries().filter(|entry| entry.end_time.is_none()).count(), 1);
+    }
+
+    #[test]
+    fn animes_in_month_by_time_watched() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+        let anime_id_3 = db.add_new_anime("My Anime 3").unwrap();
+
+        db.add_watch_entry(entry_at(anime_id_1, "02/03/2022 20:00", "02/03/2022 20:20", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "01/04/2022 20:00", "01/04/2022 23:00", "2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "31/03/2022 20:00", "31/03/2022 21:00", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_3, "19/02/2022 20:00", "19/02/2022 21:00", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_3, "19/03/2021 20:00", "19/03/2021 21:00", "2")).unwrap();
+
+        let ids: Vec<AnimeID> = db.animes_in_month(2022, 3).iter().map(|anime| anime.id()).collect();
+        assert_eq!(ids, vec![anime_id_2, anime_id_1]);
+        assert!(db.animes_in_month(2022, 5).is_empty());
+    }
+
+    #[test]
+    fn longest_session_across_midnight() {
+        let mut db = simple_database::SimpleDatabase::new();
+        assert_eq!(db.longest_session(), None);
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 21:00", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "19/03/2022 23:00", "20/03/2022 00:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "21/03/2022 20:00", "21/03/2022 21:20", "2")).unwrap();
+        // Tied with the midnight-crossing entry, but starts later
+        db.add_watch_entry(entry_at(anime_id_1, "22/03/2022 20:00", "22/03/2022 21:30", "3")).unwrap();
+        db.add_watch_entry(WatchEntry::open_ended(anime_id_1, NaiveDateTime::parse_from_str("18/03/2022 10:00", "%d/%m/%Y %H:%M").unwrap(), Episode::Number(4), None)).unwrap();
+
+        assert_eq!(db.longest_session(), Some(&entry_at(anime_id_2, "19/03/2022 23:00", "20/03/2022 00:30", "1")));
+    }
+
+    #[test]
+    fn flat_records_join_anime_names() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "19/03/2022 23:40", "20/03/2022 00:05", "3")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "21/03/2022 20:00", "21/03/2022 20:24", "2")).unwrap();
+        db.add_watch_entry(WatchEntry::open_ended(anime_id_1, NaiveDateTime::parse_from_str("22/03/2022 20:00", "%d/%m/%Y %H:%M").unwrap(), Episode::Number(3), None)).unwrap();
+
+        let records = db.flat_records();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2], WatchRecord {
+            anime_name: "My Anime 2".to_string(),
+            anime_id: anime_id_2,
+            date: NaiveDate::from_ymd_opt(2022, 3, 19).unwrap(),
+            start: NaiveDateTime::parse_from_str("19/03/2022 23:40", "%d/%m/%Y %H:%M").unwrap(),
+            end: Some(NaiveDateTime::parse_from_str("20/03/2022 00:05", "%d/%m/%Y %H:%M").unwrap()),
+            duration_minutes: Some(25),
+            episode: "03".to_string(),
+            company: None,
+        });
+        assert_eq!(records[0].anime_name, "My Anime 1");
+        assert_eq!(records[0].duration_minutes, Some(24));
+        assert_eq!(records[1].duration_minutes, None);
+    }
+
+    #[test]
+    fn most_watched_anime_by_entry_count() {
+        let mut db = simple_database::SimpleDatabase::new();
+        assert!(db.most_watched_anime().is_none());
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+        let anime_id_3 = db.add_new_anime("My Anime 3").unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "19/03/2022 21:00", "19/03/2022 21:20", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "19/03/2022 21:20", "19/03/2022 21:40", "2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_3, "20/03/2022 20:00", "20/03/2022 20:10", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_3, "20/03/2022 20:10", "20/03/2022 20:20", "2")).unwrap();
+        assert_eq!(db.most_watched_anime().map(Anime::id), Some(anime_id_2));
+
+        // Same count as the second anime, but less time
+        db.add_watch_entry(entry_at(anime_id_1, "20/03/2022 21:00", "20/03/2022 21:05", "2")).unwrap();
+        assert_eq!(db.most_watched_anime().map(Anime::id), Some(anime_id_2));
+
+        // Same count and time as the second anime, but a higher id
+        db.add_watch_entry(entry_at(anime_id_3, "20/03/2022 20:20", "20/03/2022 20:50", "3")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "21/03/2022 20:00", "21/03/2022 20:10", "3")).unwrap();
+        assert_eq!(db.most_watched_anime().map(Anime::id), Some(anime_id_2));
+
+        db.add_watch_entry(entry_at(anime_id_3, "21/03/2022 21:00", "21/03/2022 21:01", "4")).unwrap();
+        assert_eq!(db.most_watched_anime().map(Anime::id), Some(anime_id_3));
+    }
+
+    #[test]
+    fn entries_for_episode_includes_ranges() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        db.add_watch_entry(entry_at(anime_id, "20/03/2022 20:00", "20/03/2022 20:30", "5")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 21:30", "3 -> 5")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "21/03/2022 20:00", "21/03/2022 21:00", "[6,8]")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "22/03/2022 20:00", "22/03/2022 20:30", "ova")).unwrap();
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        let starts = |entries: Vec<&WatchEntry>| entries.iter().map(|entry| entry.start_time.format("%d/%m").to_string()).collect::<Vec<_>>();
+        assert_eq!(starts(anime.entries_for_episode(&Episode::Number(5))), vec!["19/03", "20/03"]);
+        assert_eq!(starts(anime.entries_for_episode(&Episode::Number(4))), vec!["19/03"]);
+        assert_eq!(starts(anime.entries_for_episode(&Episode::Number(8))), vec!["21/03"]);
+        assert!(anime.entries_for_episode(&Episode::Number(7)).is_empty());
+        assert_eq!(starts(anime.entries_for_episode(&Episode::from("OVA").unwrap())), vec!["22/03"]);
+        assert_eq!(starts(anime.entries_for_episode(&Episode::from("5 -> 6").unwrap())), vec!["19/03", "20/03", "21/03"]);
+
+        db.coalesce_sessions();
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        assert_eq!(starts(anime.entries_for_episode(&Episode::Number(5))), vec!["19/03", "20/03"]);
+    }
+
+    #[test]
+    fn entries_for_episode_with_huge_ranges() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 20:30", "1 -> 200000000")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "20/03/2022 20:00", "20/03/2022 20:30", "300000000")).unwrap();
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        assert_eq!(anime.entries_for_episode(&Episode::Number(150000000)).len(), 1);
+        assert!(anime.entries_for_episode(&Episode::Number(250000000)).is_empty());
+        assert_eq!(anime.entries_for_episode(&Episode::from("199999999 -> 2000000000").unwrap()).len(), 2);
+        assert!(anime.entries_for_episode(&Episode::from("200000001 -> 299999999").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn entries_in_range_since_span() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        db.add_watch_entry(entry_at(anime_id, "23/03/2022 20:00", "23/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "24/03/2022 00:10", "24/03/2022 00:30", "2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "31/03/2022 20:00", "31/03/2022 20:30", "3")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "01/04/2022 20:00", "01/04/2022 20:30", "4")).unwrap();
+
+        let today = NaiveDate::from_ymd_opt(2022, 3, 31).unwrap();
+        let since = span_start("7d", today).unwrap();
+        let episodes: Vec<i32> = db.entries_in_range(since, today).iter().map(|entry| entry.episode.number().unwrap()).collect();
+        assert_eq!(episodes, vec![2, 3]);
+    }
+
+    #[test]
+    fn watch_status_set_and_filtered() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+        let anime_id_3 = db.add_new_anime("My Anime 3").unwrap();
+        assert_eq!(db.animes_with_status(WatchStatus::Watching).len(), 3);
+
+        db.set_status(anime_id_2, WatchStatus::Dropped).unwrap();
+        db.set_status(anime_id_3, WatchStatus::OnHold).unwrap();
+        assert_eq!(db.set_status(9, WatchStatus::Dropped), Err(DbError::AnimeNotFound(9)));
+
+        let ids = |animes: Vec<&Anime>| animes.iter().map(|anime| anime.id()).collect::<Vec<_>>();
+        assert_eq!(ids(db.animes_with_status(WatchStatus::Watching)), vec![anime_id_1]);
+        assert_eq!(ids(db.animes_with_status(WatchStatus::Dropped)), vec![anime_id_2]);
+        assert_eq!(ids(db.animes_with_status(WatchStatus::OnHold)), vec![anime_id_3]);
+
+        db.find_anime_by_id(anime_id_1).unwrap().set_total_episodes(Some(2));
+        add_episode_entries(&mut db, anime_id_1, &["1"]);
+        assert_eq!(db.find_anime_by_id(anime_id_1).unwrap().status(), WatchStatus::Watching);
+        add_episode_entries(&mut db, anime_id_1, &["2"]);
+        assert_eq!(ids(db.animes_with_status(WatchStatus::Completed)), vec![anime_id_1]);
+
+        // Only animes still being watched complete on their own
+        db.find_anime_by_id(anime_id_2).unwrap().set_total_episodes(Some(1));
+        add_episode_entries(&mut db, anime_id_2, &["1"]);
+        assert_eq!(db.find_anime_by_id(anime_id_2).unwrap().status(), WatchStatus::Dropped);
+    }
+
+    #[test]
+    fn to_ndjson_one_object_per_entry() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My \"Anime\" 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:30", "19/03/2022 21:00", "2")).unwrap();
+        db.add_watch_entry(WatchEntry::open_ended(anime_id_2, NaiveDateTime::parse_from_str("20/03/2022 10:00", "%d/%m/%Y %H:%M").unwrap(), Episode::Number(1), None)).unwrap();
+
+        let ndjson = db.to_ndjson();
+        assert_eq!(ndjson.lines().count(), db.cached_entry_count());
+        for line in ndjson.lines() {
+            let object: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert!(object["anime"].as_str().unwrap().starts_with("My "));
+        }
+        assert!(ndjson.lines().last().unwrap().contains("\"end\":null"));
+    }
+
+    #[test]
+    fn active_days_counts_distinct_dates() {
+        let mut db = simple_database::SimpleDatabase::new();
+        assert_eq!(db.active_days(), 0);
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "19/03/2022 23:50", "20/03/2022 00:20", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "21/03/2022 20:00", "21/03/2022 20:30", "2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "25/03/2022 20:00", "25/03/2022 20:30", "3")).unwrap();
+
+        assert_eq!(db.active_days(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_json_round_trip_rebuilds_the_index() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 20:30", "1 -> 3")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "20/03/2022 20:00", "20/03/2022 20:30", "ova")).unwrap();
+
+        let mut loaded: simple_database::SimpleDatabase = serde_json::from_str(&serde_json::to_string(&db).unwrap()).unwrap();
+        let anime = loaded.find_anime_by_id(anime_id).unwrap();
+        assert_eq!(anime.entries_for_episode(&Episode::Number(2)).len(), 1);
+        assert_eq!(anime.entries_for_episode(&Episode::from("OVA").unwrap()).len(), 1);
+
+        let anime: Anime = serde_json::from_str(&serde_json::to_string(&*anime).unwrap()).unwrap();
+        assert_eq!(&anime, loaded.find_anime_by_id(anime_id).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "bincode")]
+    fn bincode_snapshot_round_trip() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1 -> 2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "19/03/2022 23:50", "20/03/2022 00:20", "ova")).unwrap();
+        db.set_status(anime_id_2, WatchStatus::Dropped).unwrap();
+        db.find_anime_by_id(anime_id_1).unwrap().add_alias("Alias");
+        db.set_timezone(Some(chrono_tz::America::Sao_Paulo));
+
+        let path = std::env::temp_dir().join(format!("rust-anime-snapshot-{}.bin", std::process::id()));
+        db.save_bincode(&path).unwrap();
+        let mut loaded = simple_database::SimpleDatabase::load_bincode(&path).unwrap();
+
+        std::fs::write(&path, b"not a snapshot").unwrap();
+        assert!(matches!(simple_database::SimpleDatabase::load_bincode(&path), Err(SnapshotError::BadMagic)));
+        std::fs::write(&path, b"RSANIME\0\x01\x00").unwrap();
+        assert!(matches!(simple_database::SimpleDatabase::load_bincode(&path), Err(SnapshotError::UnsupportedVersion(1))));
+        std::fs::remove_file(&path).unwrap();
+
+        let sorted = |db: &simple_database::SimpleDatabase| {
+            let mut animes: Vec<Anime> = db.animes().cloned().collect();
+            animes.sort_by_key(|anime| anime.id());
+            animes
+        };
+        assert_eq!(sorted(&loaded), sorted(&db));
+        assert_eq!(loaded.timezone(), Some(chrono_tz::America::Sao_Paulo));
+        assert_eq!(loaded.cached_total_watch_time(), db.cached_total_watch_time());
+        assert_eq!(loaded.find_anime_by_id(anime_id_1).unwrap().entries_for_episode(&Episode::Number(2)).len(), 1);
+        assert_eq!(loaded.add_new_anime("My Anime 3").unwrap(), db.add_new_anime("My Anime 3").unwrap());
+    }
+
+    #[test]
+    fn duplicate_entries_reported_once() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "19/03/2022 20:00", "19/03/2022 20:30", "1")).unwrap();
+
+        let duplicates = db.find_duplicate_entries();
+        assert_eq!(duplicates, vec![&entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1")]);
+    }
+
+    #[test]
+    fn time_with_counts_only_shared_entries() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+
+        let with = |entry: WatchEntry, company: &str| WatchEntry { company: Some(Company::from_str(company).unwrap()), ..entry };
+        db.add_watch_entry(with(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1"), "{Lucas Romero, Gary}")).unwrap();
+        db.add_watch_entry(with(entry_at(anime_id_2, "19/03/2022 21:00", "19/03/2022 21:45", "1"), "{Gary,  lucas romero}")).unwrap();
+        db.add_watch_entry(with(entry_at(anime_id_2, "20/03/2022 21:00", "20/03/2022 21:20", "2"), "{Gary}")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "20/03/2022 22:00", "20/03/2022 23:00", "2")).unwrap();
+
+        assert_eq!(db.time_with("Lucas Romero"), chrono::Duration::minutes(75));
+        assert_eq!(db.time_with("gary"), chrono::Duration::minutes(95));
+        assert_eq!(db.time_with("Amim"), chrono::Duration::zero());
+    }
+
+    #[test]
+    fn on_this_day_across_years() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2023 20:00", "19/03/2023 20:30", "2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2021 20:00", "19/03/2021 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "20/03/2022 20:00", "20/03/2022 20:30", "3")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "29/02/2024 20:00", "29/02/2024 20:30", "4")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "28/02/2023 20:00", "28/02/2023 20:30", "5")).unwrap();
+
+        let episodes = |entries: Vec<&WatchEntry>| entries.iter().map(|entry| entry.episode.number().unwrap()).collect::<Vec<_>>();
+        assert_eq!(episodes(db.on_this_day(3, 19)), vec![1, 2]);
+        assert_eq!(episodes(db.on_this_day(2, 29)), vec![4]);
+        assert!(db.on_this_day(12, 25).is_empty());
+    }
+
+    #[test]
+    fn coalesce_sessions_merges_resumed_episode() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        let with = |entry: WatchEntry, company: &str| WatchEntry { company: Some(Company::from_str(company).unwrap()), ..entry };
+        db.add_watch_entry(with(entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 20:10", "5"), "{Gary}")).unwrap();
+        db.add_watch_entry(with(entry_at(anime_id, "19/03/2022 20:10", "19/03/2022 20:24", "5"), "{Amim, Gary, +1}")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 20:24", "19/03/2022 20:48", "6")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 21:00", "19/03/2022 21:10", "6")).unwrap();
+        assert_eq!(db.cached_total_watch_time(), chrono::Duration::minutes(58));
+
+        db.coalesce_sessions();
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        let entries: Vec<&WatchEntry> = anime.watch_entries().collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(*entries[0], with(entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 20:24", "5"), "{Gary,Amim,+1}"));
+        assert_eq!(*entries[1], entry_at(anime_id, "19/03/2022 20:24", "19/03/2022 20:48", "6"));
+        assert_eq!(db.cached_total_watch_time(), chrono::Duration::minutes(58));
+    }
+
+    #[test]
+    fn coalesce_sessions_keeps_annotations() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        let annotated = |entry: WatchEntry, session_tag: Option<&str>, tags: &[&str], rating: Option<u8>| WatchEntry {
+            session_tag: session_tag.map(str::to_string),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            rating,
+            ..entry
+        };
+        db.add_watch_entry(annotated(entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 20:10", "5"), Some("rewatch"), &["dub"], None)).unwrap();
+        db.add_watch_entry(annotated(entry_at(anime_id, "19/03/2022 20:10", "19/03/2022 20:24", "5"), Some("rewatch"), &["bluray", "dub"], Some(8))).unwrap();
+        // Same times, but a different session: not the same viewing
+        db.add_watch_entry(annotated(entry_at(anime_id, "19/03/2022 20:24", "19/03/2022 20:30", "5"), None, &[], Some(3))).unwrap();
+
+        db.coalesce_sessions();
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        let entries: Vec<&WatchEntry> = anime.watch_entries().collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(*entries[0], annotated(entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 20:24", "5"), Some("rewatch"), &["dub", "bluray"], Some(8)));
+        assert_eq!(*entries[1], annotated(entry_at(anime_id, "19/03/2022 20:24", "19/03/2022 20:30", "5"), None, &[], Some(3)));
+    }
+
+    #[test]
+    fn search_ranks_title_alias_then_fuzzy() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let fuzzy_id = db.add_new_anime("Shingeki no Kyojin: Final Season").unwrap();
+        let alias_id = db.add_new_anime("Shingeki no Kyojin").unwrap();
+        let title_id = db.add_new_anime("Attack on Titan").unwrap();
+        let typo_id = db.add_new_anime("Attack on Titam").unwrap();
+        db.add_new_anime("Frieren").unwrap();
+        db.find_anime_by_id(alias_id).unwrap().add_alias("Attack on Titan");
+
+        let ids = |animes: Vec<&Anime>| animes.iter().map(|anime| anime.id()).collect::<Vec<_>>();
+        assert_eq!(ids(db.search("attack on titan")), vec![title_id, alias_id, typo_id]);
+        assert_eq!(ids(db.search("Shingeki no Kyojin")), vec![alias_id, fuzzy_id]);
+        assert!(db.search("Bebop").is_empty());
+        assert!(db.search("  ").is_empty());
+    }
+
+    #[test]
+    fn binge_sessions_split_on_large_gaps() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:24", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "19/03/2022 20:30", "19/03/2022 20:54", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:55", "19/03/2022 21:19", "2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "20/03/2022 14:00", "20/03/2022 14:24", "3")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "20/03/2022 14:34", "20/03/2022 14:58", "2")).unwrap();
+
+        let sessions = db.binge_sessions(chrono::Duration::minutes(10));
+        let shape: Vec<Vec<(AnimeID, i32)>> = sessions.iter()
+            .map(|session| session.iter().map(|entry| (entry.anime_id, entry.episode.number().unwrap())).collect())
+            .collect();
+        assert_eq!(shape, vec![
+            vec![(anime_id_1, 1), (anime_id_2, 1), (anime_id_1, 2)],
+            vec![(anime_id_1, 3), (anime_id_2, 2)],
+        ]);
+
+        assert_eq!(db.binge_sessions(chrono::Duration::minutes(5)).len(), 4);
+    }
+
+    #[test]
+    fn verify_integrity_reports_corruption() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "19/03/2022 21:00", "19/03/2022 21:30", "1")).unwrap();
+        assert_eq!(db.verify_integrity(), Ok(()));
+
+        db.find_anime_by_id(anime_id_1).unwrap().watch_entries[0].anime_id = anime_id_2;
+        db.find_anime_by_id(anime_id_2).unwrap().id = 7;
+        assert_eq!(db.verify_integrity(), Err(vec![
+            format!("Entry at 2022-03-19 20:00:00 of anime {} (\"My Anime 1\") points to anime {}", anime_id_1, anime_id_2),
+            format!("Anime \"My Anime 2\" has id 7 but is stored under id {}", anime_id_2),
+        ]));
+    }
+
+    #[test]
+    fn continue_watching_by_recency() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let oldest = db.add_new_anime("Oldest").unwrap();
+        let newest = db.add_new_anime("Newest").unwrap();
+        let middle = db.add_new_anime("Middle").unwrap();
+        let idle = db.add_new_anime("Idle").unwrap();
+        let dropped = db.add_new_anime("Dropped").unwrap();
+
+        db.add_watch_entry(entry_at(oldest, "01/03/2022 20:00", "01/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(newest, "10/03/2022 20:00", "10/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(newest, "18/03/2022 20:00", "18/03/2022 20:30", "2")).unwrap();
+        db.add_watch_entry(entry_at(middle, "12/03/2022 20:00", "12/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(idle, "02/01/2022 20:00", "02/01/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(dropped, "19/03/2022 20:00", "19/03/2022 20:30", "1")).unwrap();
+        db.set_status(dropped, WatchStatus::Dropped).unwrap();
+
+        let as_of = NaiveDate::parse_from_str("19/03/2022", "%d/%m/%Y").unwrap();
+        let shortlist: Vec<AnimeID> = db.continue_watching(as_of).iter().map(|anime| anime.id).collect();
+        assert_eq!(shortlist, vec![newest, middle, oldest]);
+    }
+
+    #[test]
+    fn stalled_animes_only_long_idle_unfinished() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let recent_id = db.add_new_anime("Recent").unwrap();
+        let idle_id = db.add_new_anime("Idle").unwrap();
+        let finished_id = db.add_new_anime("Finished").unwrap();
+
+        db.add_watch_entry(entry_at(recent_id, "15/03/2022 20:00", "15/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(idle_id, "02/01/2022 20:00", "02/01/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(finished_id, "02/01/2022 20:00", "02/01/2022 20:30", "1")).unwrap();
+        db.find_anime_by_id(finished_id).unwrap().set_total_episodes(Some(1));
+        db.find_anime_by_id(idle_id).unwrap().set_total_episodes(Some(12));
+
+        let as_of = NaiveDate::parse_from_str("19/03/2022", "%d/%m/%Y").unwrap();
+        let stalled: Vec<AnimeID> = db.stalled_animes(as_of, 30).iter().map(|anime| anime.id).collect();
+        assert_eq!(stalled, vec![idle_id]);
+    }
+
+    #[test]
+    fn add_anime_with_differently_cased_title_fails() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("Re:zero kara Hajimeru isekai Seikatsu").unwrap();
+        assert!(matches!(
+            db.add_new_anime("Re: Zero Kara Hajimeru Isekai Seikatsu"),
+            Err(DbError::DuplicateAnime { id, .. }) if id == anime_id
+        ));
+
+        let anime = db.find_anime_by_name("re: zero kara hajimeru isekai seikatsu:").unwrap();
+        assert_eq!(anime.id, anime_id);
+        assert_eq!(anime.name, "Re:zero kara Hajimeru isekai Seikatsu");
+    }
+
+    #[test]
+    fn add_watch_entry_to_unknown_anime_fails() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let entry = entry_at(3, "19/03/2022 20:00", "19/03/2022 20:30", "1");
+        let err = db.add_watch_entry(entry).unwrap_err();
+        assert_eq!(err, DbError::AnimeOutOfRange(3));
+        assert_eq!(err.to_string(), "Anime ID 3 is out of range");
+    }
+
+    #[test]
+    fn start_hour_histogram_buckets() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 09:10", "19/03/2022 09:40", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 22:00", "19/03/2022 22:30", "2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "20/03/2022 22:45", "20/03/2022 23:15", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "20/03/2022 23:40", "21/03/2022 00:20", "2")).unwrap();
+
+        let histogram = db.start_hour_histogram();
+        assert_eq!(histogram[9], 1);
+        assert_eq!(histogram[22], 2);
+        assert_eq!(histogram[23], 1);
+        assert_eq!(histogram[0], 0);
+        assert_eq!(histogram.iter().sum::<usize>(), 4);
+    }
+
+    #[test]
+    fn unique_episodes_watched_dedups_and_expands_ranges() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_1, "20/03/2022 20:00", "20/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "19/03/2022 21:00", "19/03/2022 22:30", "1 -> 3")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "21/03/2022 21:00", "21/03/2022 22:30", "[3,4]")).unwrap();
+
+        assert_eq!(db.unique_episodes_watched(), 5);
+    }
+
+    #[test]
+    fn half_episodes_count_but_are_not_gaps() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        add_episode_entries(&mut db, anime_id, &["6", "7", "7.5", "7.5", "9"]);
+
+        assert_eq!(db.unique_episodes_watched(), 4);
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        assert_eq!(anime.episode_count(), 4);
+        assert_eq!(anime.missing_episodes(), vec![1, 2, 3, 4, 5, 8]);
+
+        anime.set_total_episodes(Some(12));
+        assert_eq!(anime.missing_episodes(), vec![1, 2, 3, 4, 5, 8, 10, 11, 12]);
+        assert_eq!(anime.completion_percent(), Some(25.0));
+
+        let anime_id = db.add_new_anime("Only Specials").unwrap();
+        add_episode_entries(&mut db, anime_id, &["0.5"]);
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        assert_eq!((anime.episode_count(), anime.missing_episodes()), (1, vec![]));
+    }
+
+    #[test]
+    fn open_ranges_count_once_and_leave_no_gaps() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        add_episode_entries(&mut db, anime_id, &["1", "3", "5 ->"]);
+
+        let anime = db.find_anime_by_id(anime_id).unwrap();
+        anime.set_total_episodes(Some(12));
+        assert_eq!(anime.episode_count(), 3);
+        assert_eq!(anime.missing_episodes(), vec![2, 4]);
+    }
+
+    #[test]
+    fn seasonal_episodes_count_per_season() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        add_episode_entries(&mut db, anime_id, &["S1E12", "S2E01", "S2E12", "S2E01"]);
+
+        assert_eq!(db.unique_episodes_watched(), 3);
+        assert_eq!(db.find_anime_by_id(anime_id).unwrap().episode_count(), 3);
+    }
+
+    #[test]
+    fn watch_time_by_weekday_buckets() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+
+        // 19/03/2022 is a Saturday, 22/03/2022 a Tuesday
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "19/03/2022 23:40", "20/03/2022 00:20", "2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "22/03/2022 10:00", "22/03/2022 10:25", "3")).unwrap();
+
+        let by_weekday = db.watch_time_by_weekday();
+        assert_eq!(by_weekday[5], chrono::Duration::minutes(70));
+        assert_eq!(by_weekday[1], chrono::Duration::minutes(25));
+        assert_eq!(by_weekday[6], chrono::Duration::zero());
+    }
+
+    #[test]
+    fn watch_time_where_filters_entries() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        let with = |entry: WatchEntry, company: &str| WatchEntry { company: Some(Company::from_str(company).unwrap()), ..entry };
+        db.add_watch_entry(with(entry_at(anime_id, "19/03/2022 20:00", "19/03/2022 20:30", "1"), "{Gary, Amim}")).unwrap();
+        db.add_watch_entry(with(entry_at(anime_id, "19/03/2022 21:00", "19/03/2022 21:20", "2"), "{Amim}")).unwrap();
+        db.add_watch_entry(with(entry_at(anime_id, "20/03/2022 21:00", "20/03/2022 21:25", "3"), "{gary}")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "21/03/2022 21:00", "21/03/2022 21:25", "4")).unwrap();
+
+        let with_gary = db.watch_time_where(|entry| entry.company.as_ref().is_some_and(|company| company.contains("Gary")));
+        assert_eq!(with_gary, chrono::Duration::minutes(55));
+        assert_eq!(db.watch_time_where(|_| true), db.cached_total_watch_time());
+    }
+
+    #[test]
+    fn monthly_watch_time_for_a_year() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id = db.add_new_anime("My Anime").unwrap();
+        db.add_watch_entry(entry_at(anime_id, "03/01/2022 20:00", "03/01/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "31/01/2022 23:50", "01/02/2022 00:20", "2")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "14/07/2022 10:00", "14/07/2022 10:25", "3")).unwrap();
+        db.add_watch_entry(entry_at(anime_id, "14/07/2021 10:00", "14/07/2021 10:25", "4")).unwrap();
+
+        let mut expected = [chrono::Duration::zero(); 12];
+        expected[0] = chrono::Duration::minutes(60);
+        expected[6] = chrono::Duration::minutes(25);
+        assert_eq!(db.monthly_watch_time(2022), expected);
+        assert_eq!(db.monthly_watch_time(2020), [chrono::Duration::zero(); 12]);
+    }
+
+    #[test]
+    fn cached_stats_match_recomputation() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+
+        db.add_watch_entry(entry_at(anime_id_1, "19/03/2022 20:00", "19/03/2022 20:30", "1")).unwrap();
+        db.add_watch_entry(entry_at(anime_id_2, "19/03/2022 21:00", "19/03/2022 21:20", "1")).unwrap();
+        assert_eq!(db.cached_total_watch_time(), chrono::Duration::minutes(50));
+        assert_eq!(db.cached_entry_count(), 2);
+
+        db.add_watch_entry(entry_at(anime_id_2, "19/03/2022 23:40", "20/03/2022 00:20", "2")).unwrap();
+        db.remove_anime(anime_id_1).unwrap();
+        assert_eq!(db.cached_total_watch_time(), chrono::Duration::minutes(60));
+        assert_eq!(db.cached_entry_count(), 2);
+
+        // Changing entries through a mutable borrow invalidates the cache
+        db.find_anime_by_id(anime_id_2).unwrap().watch_entries.pop();
+
+        let (cached_time, cached_count) = (db.cached_total_watch_time(), db.cached_entry_count());
+        db.recompute();
+        assert_eq!(cached_time, db.cached_total_watch_time());
+        assert_eq!(cached_count, db.cached_entry_count());
+        assert_eq!(cached_time, chrono::Duration::minutes(20));
+    }
+
+    #[test]
+    fn removed_anime_id_is_not_reused() {
+        let mut db = simple_database::SimpleDatabase::new();
+
+        let anime_id_1 = db.add_new_anime("My Anime 1").unwrap();
+        let anime_id_2 = db.add_new_anime("My Anime 2").unwrap();
+        assert_eq!(db.remove_anime(anime_id_1).unwrap().name(), "My Anime 1");
+        assert_eq!(db.remove_anime(anime_id_1), Err(DbError::AnimeNotFound(anime_id_1)));
+
+        let anime_id_3 = db.add_new_anime("My Anime 3").unwrap();
+        assert_ne!(anime_id_3, anime_id_2);
+        assert_eq!(db.find_anime_by_id(anime_id_2).unwrap().name(), "My Anime 2");
+    }
+}
@@ -1,61 +1,444 @@
-use chrono::{NaiveDateTime};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use chrono::{DateTime, Duration, NaiveDateTime, NaiveTime, TimeZone};
 use regex::{Regex};
 
-#[path ="./types.rs"]
-mod types;
-use types::*;
+use crate::types::*;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub struct Episode {
-    number: i32, //TODO: support different episode types (e.g. "1.5", "[1 -> 5]", "1 -> 5", "[1,2,3,4,5]", etc.)
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Episode {
+    Number(i32),
+    /// `7.5`, a recap or special between two numbered episodes (one decimal digit)
+    Decimal { whole: i32, tenth: u8 },
+    /// `1 -> 5` or `[1 -> 5]`
+    Range { start: i32, end: i32 },
+    /// `5 ->`, from an episode onwards up to some unknown point
+    RangeFrom(i32),
+    /// `[1,2,3,4,5]`, kept sorted and without repeats, so `[3,1,1]` is `[1,3]`
+    List(Vec<i32>),
+    /// `OVA`, `SP2`: an unnumbered special, its marker (two letters or more) uppercased
+    Special(String),
+    /// `S2E05`, an episode numbered within its season
+    Seasonal { season: u32, number: i32 },
 }
 
 impl Episode {
+    /// Same as `str::parse`, kept from before `FromStr` was implemented
     pub fn from(ep_str: &str) -> Result<Self, Diagnostic> {
+        let ep_str = ep_str.trim();
+        let inner = match ep_str.strip_prefix('[') {
+            Some(rest) => rest.strip_suffix(']').ok_or_else(|| format!("Unclosed episode list: {}", ep_str))?,
+            None => ep_str,
+        };
+        let parse_number = |number: &str| number.trim().parse::<i32>().map_err(|_| format!("Invalid episode number: {}", ep_str));
+
+        if let Some((start, end)) = inner.split_once("->") {
+            if end.trim().is_empty() {
+                return Ok(Self::RangeFrom(parse_number(start)?));
+            }
+            let (start, end) = (parse_number(start)?, parse_number(end)?);
+            if end < start {
+                return Err(format!("Episode range ends before it starts: {}", ep_str));
+            }
+            // `[5 -> 5]` is just episode 5
+            return Ok(if start == end { Self::Number(start) } else { Self::Range { start, end } });
+        }
+
+        if inner.len() != ep_str.len() {
+            let mut numbers = inner.split(',').map(parse_number).collect::<Result<Vec<_>, _>>()?;
+            numbers.sort_unstable();
+            numbers.dedup();
+            return Ok(match numbers.as_slice() {
+                [number] => Self::Number(*number),
+                _ => Self::List(numbers),
+            });
+        }
+
+        if let Some((whole, tenth)) = ep_str.split_once('.') {
+            let whole = parse_number(whole)?;
+            let tenth = match tenth.as_bytes() {
+                [digit] if digit.is_ascii_digit() => digit - b'0',
+                _ => return Err(format!("Invalid decimal episode (one decimal digit expected): {}", ep_str)),
+            };
+            return Ok(match tenth {
+                0 => Self::Number(whole),
+                tenth => Self::Decimal { whole, tenth },
+            });
+        }
+
+        if let Some((season, number)) = ep_str.strip_prefix(['S', 's']).and_then(|rest| rest.split_once(['E', 'e'])) {
+            let all_digits = |part: &str| !part.is_empty() && part.bytes().all(|c| c.is_ascii_digit());
+            if all_digits(season) && all_digits(number) {
+                let season = season.parse().map_err(|_| format!("Invalid season: {}", ep_str))?;
+                return Ok(Self::Seasonal { season, number: parse_number(number)? });
+            }
+        }
+
+        let digits = ep_str.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+        if ep_str.len() - digits.len() >= 2 && digits.chars().all(|c| c.is_ascii_digit()) {
+            // Unpadded like other numbers, so `SP02` is `SP2`
+            let letters = ep_str[..ep_str.len() - digits.len()].to_ascii_uppercase();
+            let digits = match digits.trim_start_matches('0') {
+                "" if !digits.is_empty() => "0",
+                digits => digits,
+            };
+            return Ok(Self::Special(letters + digits));
+        }
+
         let number = ep_str.parse().map_err(|_| format!("Invalid episode number: {}", ep_str))?;
-        Ok(Self { number })
+        Ok(Self::Number(number))
     } 
+
+    /// Number of a single episode, `None` for ranges and lists.
+    ///
+    /// ```
+    /// use rust_anime::elements::Episode;
+    ///
+    /// let episode = Episode::from("07").unwrap();
+    /// assert_eq!(episode.number(), Some(7));
+    /// ```
+    pub fn number(&self) -> Option<i32> {
+        match self {
+            Self::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// First episode covered (the episode itself for single episodes, `7` for `7.5`)
+    pub fn start(&self) -> Option<i32> {
+        match self {
+            Self::Number(number) => Some(*number),
+            Self::Decimal { whole, .. } => Some(*whole),
+            Self::Range { start, .. } | Self::RangeFrom(start) => Some(*start),
+            Self::List(numbers) => numbers.iter().min().copied(),
+            Self::Seasonal { number, .. } => Some(*number),
+            Self::Special(_) => None,
+        }
+    }
+
+    /// Last episode covered (the episode itself for single episodes, `7` for `7.5`)
+    pub fn end(&self) -> Option<i32> {
+        match self {
+            Self::Number(number) => Some(*number),
+            Self::Decimal { whole, .. } => Some(*whole),
+            Self::Range { end, .. } => Some(*end),
+            Self::List(numbers) => numbers.iter().max().copied(),
+            Self::Seasonal { number, .. } => Some(*number),
+            Self::RangeFrom(_) | Self::Special(_) => None,
+        }
+    }
+
+    /// Season of a seasonal episode (`2` for `S2E05`)
+    pub fn season(&self) -> Option<u32> {
+        match self {
+            Self::Seasonal { season, .. } => Some(*season),
+            _ => None,
+        }
+    }
+
+    /// Whether this is an opening or ending (`OP`, `ED2`), which take a couple of minutes at most
+    pub fn is_short_form(&self) -> bool {
+        match self {
+            Self::Special(marker) => ["OP", "ED"].iter()
+                .any(|prefix| marker.strip_prefix(prefix).is_some_and(|rest| rest.bytes().all(|c| c.is_ascii_digit()))),
+            _ => false,
+        }
+    }
+
+    /// Every numbered episode covered, ranges and lists expanded (none for `7.5`, specials or seasonal episodes).
+    /// Only the first episode of `5 ->` is known to be covered.
+    pub fn numbers(&self) -> Vec<i32> {
+        match self {
+            Self::Number(number) | Self::RangeFrom(number) => vec![*number],
+            Self::Decimal { .. } | Self::Special(_) | Self::Seasonal { .. } => vec![],
+            Self::Range { start, end } => (*start..=*end).collect(),
+            Self::List(numbers) => numbers.clone(),
+        }
+    }
+
+    /// The episode after this one, `None` unless it is a single numbered (or half) episode
+    pub fn next(&self) -> Option<Episode> {
+        self.succ_n(1)
+    }
+
+    /// The `n`th episode after this one; `7.5` counts from `7` and `S2E05` stays in season 2
+    pub fn succ_n(&self, n: i32) -> Option<Episode> {
+        match self {
+            Self::Number(number) | Self::Decimal { whole: number, .. } => number.checked_add(n).map(Self::Number),
+            Self::Seasonal { season, number } => number.checked_add(n).map(|number| Self::Seasonal { season: *season, number }),
+            _ => None,
+        }
+    }
+
+    /// Position for ordering episodes: by season (unseasoned first), then `7.5` sorting between `7` and `8`
+    pub fn sort_key(&self) -> (Option<u32>, i32, u8) {
+        match self {
+            Self::Decimal { whole, tenth } => (None, *whole, *tenth),
+            _ => (self.season(), self.start().unwrap_or(i32::MIN), 0),
+        }
+    }
+
+    /// Every episode viewed, as sort keys: `7.5` counts as one episode of its own, specials as none,
+    /// and `S1E12`/`S2E12` as different episodes
+    pub fn viewed(&self) -> Vec<(Option<u32>, i32, u8)> {
+        match self {
+            Self::Decimal { .. } | Self::Seasonal { .. } => vec![self.sort_key()],
+            _ => self.numbers().into_iter().map(|number| (None, number, 0)).collect(),
+        }
+    }
+}
+
+impl fmt::Display for Episode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(number) => write!(f, "{:02}", number),
+            Self::Decimal { whole, tenth } => write!(f, "{:02}.{}", whole, tenth),
+            Self::Range { start, end } => write!(f, "{:02} -> {:02}", start, end),
+            Self::RangeFrom(start) => write!(f, "{:02} ->", start),
+            Self::List(numbers) => write!(f, "[{}]", numbers.iter().map(|number| format!("{:02}", number)).collect::<Vec<_>>().join(",")),
+            Self::Special(marker) => write!(f, "{}", marker),
+            Self::Seasonal { season, number } => write!(f, "S{}E{:02}", season, number),
+        }
+    }
 }
 
+/// Why an episode couldn't be parsed, e.g. `"1 -> x".parse::<Episode>()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpisodeParseError(pub Diagnostic);
 
-#[derive(Debug, PartialEq, Clone)]
+impl fmt::Display for EpisodeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for EpisodeParseError {}
+
+impl FromStr for Episode {
+    type Err = EpisodeParseError;
+
+    fn from_str(ep_str: &str) -> Result<Self, Self::Err> {
+        Episode::from(ep_str).map_err(EpisodeParseError)
+    }
+}
+
+static COMPANY_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\{(.*)\}$").unwrap());
+static COMPANY_TAG_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\[\s*with:(.*)\]$").unwrap());
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Company {
-    names: Vec<String>
+    names: Vec<String>,
+    /// Unnamed co-watchers, written as `+N`
+    anonymous: u32,
+    /// Explicitly watched alone, written as `{solo}` or `{-}`
+    solo: bool,
 }
 
 
+/// Why a company couldn't be parsed, e.g. `"{Gary,,Amim}".parse::<Company>()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompanyParseError {
+    /// Not wrapped in `{...}`
+    MissingBraces(String),
+    /// A member left empty between separators (a single trailing separator is fine).
+    /// Only `str::parse` reports it: the inherent `Company::from_str` skips empty members.
+    EmptyName(String),
+    /// `+N` with something other than a number
+    InvalidAnonymousCount(String),
+}
+
+impl fmt::Display for CompanyParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingBraces(company_str) => write!(f, "String does not match company format: \"{}\" instead of r\"{{(.*)}}\"", company_str),
+            Self::EmptyName(company_str) => write!(f, "Empty name in company: \"{}\"", company_str),
+            Self::InvalidAnonymousCount(count) => write!(f, "Invalid anonymous company count: \"{}\"", count),
+        }
+    }
+}
+
+impl std::error::Error for CompanyParseError {}
+
+impl FromStr for Company {
+    type Err = CompanyParseError;
+
+    fn from_str(company_str: &str) -> Result<Self, Self::Err> {
+        Self::parse_with_separator(company_str, ',', true)
+    }
+}
+
 impl Company {
+    /// Like `str::parse` with the error as a message, but empty members (`{Gary,,Amim}`) are skipped
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(company_str: &str) -> Result<Self, Diagnostic> {
-        if !Regex::new(r"^\{(.*)\}$").unwrap().is_match(company_str) {
-            return Err(format!("String does not match company format: \"{}\" instead of r\"{{(.*)}}\"", company_str));
+        Self::from_str_with_separator(company_str, ',')
+    }
+
+    /// Like `from_str`, with members separated by `separator` (names never contain it)
+    pub fn from_str_with_separator(company_str: &str, separator: char) -> Result<Self, Diagnostic> {
+        Self::parse_with_separator(company_str, separator, false).map_err(|e| e.to_string())
+    }
+
+    /// With `strict`, an empty member is an error instead of being skipped
+    fn parse_with_separator(company_str: &str, separator: char, strict: bool) -> Result<Self, CompanyParseError> {
+        if !COMPANY_REGEX.is_match(company_str) {
+            return Err(CompanyParseError::MissingBraces(company_str.to_string()));
         }
 
         // Drop the braces
-        let company_str = &company_str[1..company_str.len()-1];
-        let company_str = company_str.trim();
-
-        let names = match company_str.trim() {
-            "" => vec![],
-            _ => company_str.split(',')
-                    .filter(|s| !s.trim().is_empty())
-                    .map(str::to_string)
-                    .collect()
-        };
-        Ok(Self { names })
+        let members = &company_str[1..company_str.len()-1];
+        let members = members.trim();
+        if members == "-" || members.eq_ignore_ascii_case("solo") {
+            return Ok(Self::solo());
+        }
+
+        let mut names = vec![];
+        let mut anonymous = 0;
+        if !members.is_empty() {
+            let members = members.strip_suffix(separator).unwrap_or(members);
+            for name in members.split(separator) {
+                if name.trim().is_empty() {
+                    if strict {
+                        return Err(CompanyParseError::EmptyName(company_str.to_string()));
+                    }
+                    continue;
+                }
+                match name.trim().strip_prefix('+') {
+                    Some(count) => anonymous += count.parse::<u32>().map_err(|_| CompanyParseError::InvalidAnonymousCount(name.trim().to_string()))?,
+                    None => names.push(name.trim().to_string()),
+                }
+            }
+        }
+
+        Ok(Self { names, anonymous, solo: false })
+    }
+
+    /// Watched alone on purpose, as opposed to company not being recorded (`None`)
+    pub fn solo() -> Self {
+        Self { names: vec![], anonymous: 0, solo: true }
+    }
+
+    pub fn is_solo(&self) -> bool {
+        self.solo
+    }
+
+    /// Older logs wrote company as a tag: `[with: Gary, Amim]`
+    pub fn from_tag(tag_str: &str) -> Result<Self, Diagnostic> {
+        Self::from_tag_with_separator(tag_str, ',')
+    }
+
+    pub fn from_tag_with_separator(tag_str: &str, separator: char) -> Result<Self, Diagnostic> {
+        let members = COMPANY_TAG_REGEX
+            .captures(tag_str)
+            .and_then(|caps| caps.get(1))
+            .ok_or_else(|| format!("String does not match company tag format: \"{}\" instead of r\"[with:(.*)]\"", tag_str))?;
+
+        Self::from_str_with_separator(&format!("{{{}}}", members.as_str().trim()), separator)
     }
 
     fn iter(&self) -> impl Iterator<Item = &String> {
         self.names.iter()
     }
+
+    /// Whether `name` is among the named members, ignoring case and spacing
+    pub fn contains(&self, name: &str) -> bool {
+        let name = normalize_title(name);
+        self.iter().any(|member| normalize_title(member) == name)
+    }
+
+    /// Adds `other`'s named members not already present; anonymous counts keep the larger one
+    pub fn merge(&mut self, other: &Company) {
+        for name in other.iter() {
+            if !self.contains(name) {
+                self.names.push(name.clone());
+            }
+        }
+        self.anonymous = self.anonymous.max(other.anonymous);
+        self.solo = self.solo && other.solo;
+    }
+
+    /// Named members, as written
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn anonymous(&self) -> u32 {
+        self.anonymous
+    }
+
+    /// Number of co-watchers, named and anonymous
+    pub fn len(&self) -> usize {
+        self.names.len() + self.anonymous as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+impl fmt::Display for Company {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.solo {
+            return write!(f, "{{solo}}");
+        }
+        let mut members = self.names.clone();
+        if self.anonymous > 0 {
+            members.push(format!("+{}", self.anonymous));
+        }
+        write!(f, "{{{}}}", members.join(","))
+    }
+}
+
+#[derive(Debug, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WatchEntry {
     pub anime_id: AnimeID,   
     pub start_time: NaiveDateTime,
-    pub end_time: NaiveDateTime,
+    /// `None` when only the start was logged
+    pub end_time: Option<NaiveDateTime>,
     pub episode: Episode,
     pub company: Option<Company>,
+    pub session_tag: Option<String>,
+    /// Bracketed tags on the watch line itself, e.g. `dub` or `src(bluray)`
+    pub tags: Vec<String>,
+    /// Score out of 10 given to what was watched, if any. The log format has no syntax for it, so it is
+    /// only set through `WatchEntryBuilder::rating`, and no report writes it out.
+    pub rating: Option<u8>,
+    /// Line of the log the entry was parsed from, under `ParserConfig::keep_source_lines`;
+    /// ignored by equality and hashing
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub source_line: Option<String>,
+}
+
+impl PartialEq for WatchEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.anime_id == other.anime_id
+            && self.start_time == other.start_time
+            && self.end_time == other.end_time
+            && self.episode == other.episode
+            && self.company == other.company
+            && self.session_tag == other.session_tag
+            && self.tags == other.tags
+            && self.rating == other.rating
+    }
+}
+
+impl Hash for WatchEntry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.anime_id.hash(state);
+        self.start_time.hash(state);
+        self.end_time.hash(state);
+        self.episode.hash(state);
+        self.company.hash(state);
+        self.session_tag.hash(state);
+        self.tags.hash(state);
+        self.rating.hash(state);
+    }
 }
 
 impl WatchEntry {
@@ -63,10 +446,173 @@ impl WatchEntry {
         Self {
             anime_id,
             start_time,
-            end_time,
+            end_time: Some(end_time),
             episode,
             company,
+            session_tag: None,
+            tags: vec![],
+            rating: None,
+            source_line: None,
+        }
+    }
+
+    /// Entry with a start time but no logged end
+    pub fn open_ended(anime_id: AnimeID, start_time: NaiveDateTime, episode: Episode, company: Option<Company>) -> Self {
+        Self {
+            end_time: None,
+            ..Self::new(anime_id, start_time, start_time, episode, company)
+        }
+    }
+
+    /// Like `new`, but rejects entries whose end is before their start, or equal to it
+    pub fn try_new(anime_id: AnimeID, start_time: NaiveDateTime, end_time: NaiveDateTime, episode: Episode, company: Option<Company>) -> Result<Self, Diagnostic> {
+        if end_time < start_time {
+            return Err(format!("End time {} is before start time {}", end_time, start_time));
+        }
+        if end_time == start_time {
+            return Err(format!("Entry at {} has zero duration", start_time));
+        }
+
+        Ok(Self::new(anime_id, start_time, end_time, episode, company))
+    }
+
+    /// Time between start and end, `None` for open-ended entries
+    pub fn duration(&self) -> Option<Duration> {
+        self.end_time.map(|end_time| end_time - self.start_time)
+    }
+
+    /// Same anime, times and episode; annotations like company and session tag are ignored
+    pub fn same_session(&self, other: &WatchEntry) -> bool {
+        (self.anime_id, self.start_time, self.end_time, &self.episode) == (other.anime_id, other.start_time, other.end_time, &other.episode)
+    }
+
+    /// Start time read as a local time in `tz` (None if it doesn't exist there, e.g. inside a DST gap)
+    pub fn start_time_in<Tz: TimeZone>(&self, tz: &Tz) -> Option<DateTime<Tz>> {
+        tz.from_local_datetime(&self.start_time).earliest()
+    }
+
+    /// End time read as a local time in `tz` (None if it doesn't exist there, e.g. inside a DST gap)
+    pub fn end_time_in<Tz: TimeZone>(&self, tz: &Tz) -> Option<DateTime<Tz>> {
+        self.end_time.and_then(|end_time| tz.from_local_datetime(&end_time).earliest())
+    }
+
+    /// Splits an entry crossing midnight into one entry per day (ending at 23:59:59 and
+    /// restarting at 00:00:00); other entries come back unchanged as a single element.
+    /// An entry ending exactly at midnight gets no empty part on the day it ends.
+    pub fn split_at_midnight(&self) -> Vec<WatchEntry> {
+        let end_time = match self.end_time {
+            Some(end_time) if end_time.date() != self.start_time.date() => end_time,
+            _ => return vec![self.clone()],
+        };
+
+        let last_second = NaiveTime::from_hms_opt(23, 59, 59).unwrap();
+        let mut parts = vec![];
+        let mut part_start = self.start_time;
+        while part_start.date() < end_time.date() {
+            parts.push(WatchEntry {
+                start_time: part_start,
+                end_time: Some(part_start.date().and_time(last_second)),
+                ..self.clone()
+            });
+            part_start = (part_start.date() + Duration::days(1)).and_time(NaiveTime::MIN);
+        }
+        if part_start < end_time {
+            parts.push(WatchEntry {
+                start_time: part_start,
+                end_time: Some(end_time),
+                ..self.clone()
+            });
+        }
+
+        parts
+    }
+
+    pub fn builder() -> WatchEntryBuilder {
+        WatchEntryBuilder::new()
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct WatchEntryBuilder {
+    anime_id: Option<AnimeID>,
+    start_time: Option<NaiveDateTime>,
+    end_time: Option<NaiveDateTime>,
+    episode: Option<Episode>,
+    company: Option<Company>,
+    rating: Option<u8>,
+    allow_midnight_crossing: bool,
+    allow_zero_duration: bool,
+}
+
+impl WatchEntryBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn anime_id(mut self, anime_id: AnimeID) -> Self {
+        self.anime_id = Some(anime_id);
+        self
+    }
+
+    pub fn start(mut self, start_time: NaiveDateTime) -> Self {
+        self.start_time = Some(start_time);
+        self
+    }
+
+    pub fn end(mut self, end_time: NaiveDateTime) -> Self {
+        self.end_time = Some(end_time);
+        self
+    }
+
+    pub fn episode(mut self, episode: Episode) -> Self {
+        self.episode = Some(episode);
+        self
+    }
+
+    pub fn company(mut self, company: Option<Company>) -> Self {
+        self.company = company;
+        self
+    }
+
+    /// Score out of 10; `build` rejects anything higher
+    pub fn rating(mut self, rating: u8) -> Self {
+        self.rating = Some(rating);
+        self
+    }
+
+    /// When set, an end before the start is read as ending on the following day
+    pub fn allow_midnight_crossing(mut self, allow: bool) -> Self {
+        self.allow_midnight_crossing = allow;
+        self
+    }
+
+    /// When set, an end equal to the start is kept as a zero-length marker instead of rejected
+    pub fn allow_zero_duration(mut self, allow: bool) -> Self {
+        self.allow_zero_duration = allow;
+        self
+    }
+
+    pub fn build(self) -> Result<WatchEntry, Diagnostic> {
+        let anime_id = self.anime_id.ok_or_else(|| "No anime id!".to_string())?;
+        let start_time = self.start_time.ok_or_else(|| "No start time!".to_string())?;
+        let episode = self.episode.ok_or_else(|| "No episode!".to_string())?;
+        if let Some(rating) = self.rating.filter(|&rating| rating > 10) {
+            return Err(format!("Rating {} is not between 0 and 10", rating));
         }
+        let rated = |entry: WatchEntry| WatchEntry { rating: self.rating, ..entry };
+        let mut end_time = match self.end_time {
+            Some(end_time) => end_time,
+            None => return Ok(rated(WatchEntry::open_ended(anime_id, start_time, episode, self.company))),
+        };
+
+        if end_time < start_time && self.allow_midnight_crossing {
+            end_time += Duration::days(1);
+        }
+        if end_time == start_time && self.allow_zero_duration {
+            return Ok(rated(WatchEntry::new(anime_id, start_time, end_time, episode, self.company)));
+        }
+
+        WatchEntry::try_new(anime_id, start_time, end_time, episode, self.company).map(rated)
     }
 }
 
@@ -77,19 +623,19 @@ mod tests {
     #[test]
     fn company_from_str() {
         let company = Company::from_str("{Konami,Square Enix}").unwrap();
-        let expected = Company { names: vec!["Konami".to_string(), "Square Enix".to_string()] };
+        let expected = Company { names: vec!["Konami".to_string(), "Square Enix".to_string()], anonymous: 0, solo: false };
         assert_eq!(company, expected);
 
         let company = Company::from_str("{Konami}").unwrap();
-        let expected = Company { names: vec!["Konami".to_string()] };
+        let expected = Company { names: vec!["Konami".to_string()], anonymous: 0, solo: false };
         assert_eq!(company, expected);
 
         let company = Company::from_str("{}").unwrap();
-        let expected = Company { names: vec![] };
+        let expected = Company { names: vec![], anonymous: 0, solo: false };
         assert_eq!(company, expected);
 
         let company = Company::from_str("{Konami,Square Enix,}").unwrap();
-        let expected = Company { names: vec!["Konami".to_string(), "Square Enix".to_string()] };
+        let expected = Company { names: vec!["Konami".to_string(), "Square Enix".to_string()], anonymous: 0, solo: false };
         assert_eq!(company, expected);
 
         // let company = Company::from_str("");
@@ -100,23 +646,23 @@ mod tests {
     #[test]
     fn episode_from_str() {
         let episode = Episode::from("1").unwrap();
-        let expected = Episode { number: 1 };
+        let expected = Episode::Number(1);
         assert_eq!(episode, expected);
 
         let episode = Episode::from("01").unwrap();
-        let expected = Episode { number: 1 };
+        let expected = Episode::Number(1);
         assert_eq!(episode, expected);
 
         let episode = Episode::from("001").unwrap();
-        let expected = Episode { number: 1 };
+        let expected = Episode::Number(1);
         assert_eq!(episode, expected);
 
         let episode = Episode::from("-1").unwrap();
-        let expected = Episode { number: -1 };
+        let expected = Episode::Number(-1);
         assert_eq!(episode, expected);
 
         let episode = Episode::from("-01").unwrap();
-        let expected = Episode { number: -1 };
+        let expected = Episode::Number(-1);
         assert_eq!(episode, expected);
 
         let episode = Episode::from("a");
@@ -125,8 +671,442 @@ mod tests {
         let episode = Episode::from("1a");
         assert!(episode.is_err());
 
-        let episode = Episode::from("1.1");
+        let episode = Episode::from("1.1.1");
         assert!(episode.is_err());
     }
-}
 
+    #[test]
+    fn special_episode_canonical_marker() {
+        let expected = Episode::Special("OVA".to_string());
+        for marker in ["ova", "OVA", "Ova", " oVa "] {
+            assert_eq!(Episode::from(marker).unwrap(), expected, "{:?}", marker);
+        }
+        assert_eq!(Episode::from("sp2").unwrap(), Episode::Special("SP2".to_string()));
+        assert!(Episode::from("op").unwrap().is_short_form() && Episode::from("ED2").unwrap().is_short_form());
+        assert!(!Episode::from("OVA").unwrap().is_short_form() && !Episode::from("05").unwrap().is_short_form());
+        assert!(Episode::from("s2").is_err());
+        assert!(Episode::from("ova2b").is_err());
+
+        for marker in ["ova", "Sp2"] {
+            let episode = Episode::from(marker).unwrap();
+            assert_eq!(Episode::from(&episode.to_string()).unwrap(), episode);
+        }
+        assert_eq!(expected.to_string(), "OVA");
+        assert_eq!(expected.number(), None);
+        assert!(expected.numbers().is_empty());
+    }
+
+    #[test]
+    fn episode_from_str_trims_whitespace() {
+        for episode in [" 12", "12 ", " 12 ", "\t12\n"] {
+            assert_eq!(Episode::from(episode).unwrap(), Episode::Number(12), "{:?}", episode);
+        }
+        assert_eq!(Episode::from(" [1 -> 5] ").unwrap(), Episode::Range { start: 1, end: 5 });
+
+        assert!(Episode::from("1 2").is_err());
+        assert!(Episode::from(" ").is_err());
+    }
+
+    #[test]
+    fn episode_range_and_list_from_str() {
+        assert_eq!(Episode::from("1 -> 5").unwrap(), Episode::Range { start: 1, end: 5 });
+        assert_eq!(Episode::from("[1 -> 5]").unwrap(), Episode::Range { start: 1, end: 5 });
+
+        let open = Episode::from("5 ->").unwrap();
+        assert_eq!(open, Episode::RangeFrom(5));
+        assert_eq!((open.start(), open.end(), open.numbers(), open.to_string()), (Some(5), None, vec![5], "05 ->".to_string()));
+        assert_eq!(Episode::from("5 -> 8").unwrap(), Episode::Range { start: 5, end: 8 });
+        assert!(Episode::from("-> 8").is_err());
+        assert_eq!(Episode::from("[1,2,4]").unwrap(), Episode::List(vec![1, 2, 4]));
+        assert_eq!(Episode::from("[1, 2, 4]").unwrap(), Episode::List(vec![1, 2, 4]));
+
+        assert!(Episode::from("5 -> 1").is_err());
+        assert!(Episode::from("[1,2").is_err());
+        assert!(Episode::from("[1,a]").is_err());
+
+        let range = Episode::from("3 -> 5").unwrap();
+        assert_eq!(range.number(), None);
+        assert_eq!((range.start(), range.end()), (Some(3), Some(5)));
+        assert_eq!(range.numbers(), vec![3, 4, 5]);
+        assert_eq!(Episode::from("[4,2]").unwrap().numbers(), vec![2, 4]);
+    }
+
+    #[test]
+    fn episode_display_round_trips() {
+        for episode in ["07", "01 -> 05", "[01,03,04]"] {
+            assert_eq!(Episode::from(episode).unwrap().to_string(), episode);
+        }
+    }
+
+    #[test]
+    fn episode_from_str_trait() {
+        assert_eq!("12".parse::<Episode>(), Ok(Episode::Number(12)));
+        assert_eq!(" [1 -> 3] ".parse::<Episode>(), Ok(Episode::Range { start: 1, end: 3 }));
+
+        let err = "1 -> x".parse::<Episode>().unwrap_err();
+        assert_eq!(err.to_string(), Episode::from("1 -> x").unwrap_err());
+    }
+
+    #[test]
+    fn episode_equality_ignores_spelling() {
+        // Spellings within a group are the same episode, and different from every other group
+        let groups: &[&[&str]] = &[
+            &["1", "01", " 001 ", "[1]", "[01 -> 01]", "1.0", "[1,1]", "[01, 1]"],
+            &["2"],
+            &["7.5", "07.5"],
+            &["1 -> 5", "01 -> 05", "[1->5]", " [ 01 -> 05 ] "],
+            &["1 -> 6"],
+            &["5 ->", "05 ->", "5->"],
+            &["[1,3]", "[01,03]", "[ 1, 3 ]", "[3,1]", "[3,1,3]"],
+            &["[1,2,3]"],
+            &["ova", "OVA", "Ova"],
+            &["sp2", "SP02", "Sp2"],
+            &["SP20"],
+            &["S2E05", "s2e5", "S02E005"],
+            &["S1E05"],
+        ];
+
+        let parsed: Vec<Vec<Episode>> = groups.iter()
+            .map(|group| group.iter().map(|spelling| Episode::from(spelling).unwrap_or_else(|e| panic!("{}: {}", spelling, e))).collect())
+            .collect();
+        for (i, group) in parsed.iter().enumerate() {
+            for (j, episode) in group.iter().enumerate() {
+                assert_eq!(episode, &group[0], "{:?} vs {:?}", groups[i][j], groups[i][0]);
+            }
+            for (other, other_group) in parsed.iter().enumerate().filter(|(other, _)| *other != i) {
+                assert_ne!(group[0], other_group[0], "{:?} vs {:?}", groups[i][0], groups[other][0]);
+            }
+        }
+    }
+
+    #[test]
+    fn episode_decimal_from_str() {
+        assert_eq!(Episode::from("7.5").unwrap(), Episode::Decimal { whole: 7, tenth: 5 });
+        assert_eq!(Episode::from("07.5").unwrap(), Episode::Decimal { whole: 7, tenth: 5 });
+        assert_eq!(Episode::from("7.0").unwrap(), Episode::Number(7));
+        assert!(Episode::from("7.25").is_err());
+        assert!(Episode::from("7.").is_err());
+        assert!(Episode::from("7.a").is_err());
+
+        let half = Episode::from("7.5").unwrap();
+        assert_eq!((half.number(), half.start(), half.end()), (None, Some(7), Some(7)));
+        assert!(half.numbers().is_empty());
+        assert_eq!(half.viewed(), vec![(None, 7, 5)]);
+        assert_eq!(half.to_string(), "07.5");
+
+        let mut episodes: Vec<Episode> = ["8", "7.5", "7"].iter().map(|episode| Episode::from(episode).unwrap()).collect();
+        episodes.sort_by_key(Episode::sort_key);
+        assert_eq!(episodes, vec![Episode::Number(7), half, Episode::Number(8)]);
+    }
+
+    #[test]
+    fn episode_seasonal_from_str() {
+        let episode = Episode::from("S2E05").unwrap();
+        assert_eq!(episode, Episode::Seasonal { season: 2, number: 5 });
+        assert_eq!(Episode::from("s2e5").unwrap(), episode);
+        assert_eq!(episode.season(), Some(2));
+        assert_eq!((episode.start(), episode.end()), (Some(5), Some(5)));
+        assert_eq!(episode.to_string(), "S2E05");
+        assert_eq!(Episode::from(&episode.to_string()).unwrap(), episode);
+        assert_eq!(episode.next(), Some(Episode::Seasonal { season: 2, number: 6 }));
+        assert!(Episode::from("S2E").is_err());
+        assert!(Episode::from("S2E5x").is_err());
+
+        let mut episodes: Vec<Episode> = ["S2E05", "S1E13", "S2E01", "12"].iter().map(|episode| Episode::from(episode).unwrap()).collect();
+        episodes.sort_by_key(Episode::sort_key);
+        let episodes: Vec<String> = episodes.iter().map(ToString::to_string).collect();
+        assert_eq!(episodes, vec!["12", "S1E13", "S2E01", "S2E05"]);
+
+        assert_ne!(Episode::from("S1E12").unwrap().viewed(), Episode::from("S2E12").unwrap().viewed());
+    }
+
+    #[test]
+    fn episode_next_and_succ_n() {
+        assert_eq!(Episode::from("7").unwrap().next(), Some(Episode::Number(8)));
+        assert_eq!(Episode::from("7").unwrap().succ_n(3), Some(Episode::Number(10)));
+        assert_eq!(Episode::from("7.5").unwrap().next(), Some(Episode::Number(8)));
+        assert_eq!(Episode::Number(i32::MAX).next(), None);
+
+        assert_eq!(Episode::from("1 -> 3").unwrap().next(), None);
+        assert_eq!(Episode::from("[1,3]").unwrap().succ_n(2), None);
+    }
+
+    #[test]
+    fn episode_range_padding_and_spacing() {
+        let expected = Episode::from("1 -> 5").unwrap();
+        for start in ["1", "01", "001"] {
+            for end in ["5", "05"] {
+                for arrow in ["->", " -> ", " ->", "-> ", "  ->  "] {
+                    let range = format!("{}{}{}", start, arrow, end);
+                    assert_eq!(Episode::from(&range).unwrap(), expected, "{}", range);
+                    assert_eq!(Episode::from(&format!("[{}]", range)).unwrap(), expected, "[{}]", range);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn watch_entry_builder_ok() {
+        let start = DateTime::from_timestamp(0, 0).unwrap().naive_utc();
+        let end = DateTime::from_timestamp(60, 0).unwrap().naive_utc();
+
+        let entry = WatchEntry::builder()
+            .anime_id(1)
+            .start(start)
+            .end(end)
+            .episode(Episode::from("3").unwrap())
+            .company(Some(Company::from_str("{Gary}").unwrap()))
+            .build()
+            .unwrap();
+
+        let expected = WatchEntry::new(1, start, end, Episode::from("3").unwrap(), Some(Company::from_str("{Gary}").unwrap()));
+        assert_eq!(entry, expected);
+    }
+
+    #[test]
+    fn watch_entry_builder_swapped_times_fail() {
+        let start = DateTime::from_timestamp(60, 0).unwrap().naive_utc();
+        let end = DateTime::from_timestamp(0, 0).unwrap().naive_utc();
+
+        let entry = WatchEntry::builder()
+            .anime_id(1)
+            .start(start)
+            .end(end)
+            .episode(Episode::from("3").unwrap())
+            .build();
+        assert!(entry.is_err());
+    }
+
+    #[test]
+    fn watch_entry_builder_rating() {
+        let start = DateTime::from_timestamp(0, 0).unwrap().naive_utc();
+        let builder = WatchEntry::builder().anime_id(1).start(start).episode(Episode::from("3").unwrap());
+
+        assert_eq!(builder.clone().build().unwrap().rating, None);
+        assert_eq!(builder.clone().rating(10).build().unwrap().rating, Some(10));
+        assert_eq!(builder.clone().rating(0).end(start + Duration::minutes(20)).build().unwrap().rating, Some(0));
+        assert_eq!(builder.rating(11).build(), Err("Rating 11 is not between 0 and 10".to_string()));
+    }
+
+    #[test]
+    fn watch_entry_builder_midnight_crossing() {
+        let start = NaiveDateTime::parse_from_str("10/02/2022 23:40", "%d/%m/%Y %H:%M").unwrap();
+        let end = NaiveDateTime::parse_from_str("10/02/2022 00:20", "%d/%m/%Y %H:%M").unwrap();
+
+        let entry = WatchEntry::builder()
+            .anime_id(1)
+            .start(start)
+            .end(end)
+            .episode(Episode::from("3").unwrap())
+            .allow_midnight_crossing(true)
+            .build()
+            .unwrap();
+        assert_eq!(entry.end_time, Some(NaiveDateTime::parse_from_str("11/02/2022 00:20", "%d/%m/%Y %H:%M").unwrap()));
+    }
+
+    #[test]
+    fn watch_entry_try_new_rejects_negative_duration() {
+        let start = DateTime::from_timestamp(60, 0).unwrap().naive_utc();
+        let end = DateTime::from_timestamp(0, 0).unwrap().naive_utc();
+
+        let entry = WatchEntry::try_new(1, start, end, Episode::from("1").unwrap(), None);
+        assert!(entry.is_err());
+
+        let entry = WatchEntry::try_new(1, end, start, Episode::from("1").unwrap(), None);
+        assert_eq!(entry, Ok(WatchEntry::new(1, end, start, Episode::from("1").unwrap(), None)));
+    }
+
+    #[test]
+    fn watch_entry_zero_duration_only_when_allowed() {
+        let start = DateTime::from_timestamp(60, 0).unwrap().naive_utc();
+        let builder = WatchEntry::builder().anime_id(1).start(start).end(start).episode(Episode::from("5").unwrap());
+
+        assert!(WatchEntry::try_new(1, start, start, Episode::from("5").unwrap(), None).is_err());
+        assert!(builder.clone().build().is_err());
+
+        let entry = builder.allow_zero_duration(true).build().unwrap();
+        assert_eq!(entry.duration(), Some(Duration::zero()));
+    }
+
+    #[test]
+    fn watch_entry_times_in_timezones() {
+        let start = NaiveDateTime::parse_from_str("19/03/2022 16:40", "%d/%m/%Y %H:%M").unwrap();
+        let end = NaiveDateTime::parse_from_str("19/03/2022 18:24", "%d/%m/%Y %H:%M").unwrap();
+        let entry = WatchEntry::new(1, start, end, Episode::from("1").unwrap(), None);
+
+        let sao_paulo = chrono_tz::America::Sao_Paulo;
+        assert_eq!(entry.start_time_in(&sao_paulo).unwrap().to_rfc3339(), "2022-03-19T16:40:00-03:00");
+        assert_eq!(entry.end_time_in(&sao_paulo).unwrap().to_rfc3339(), "2022-03-19T18:24:00-03:00");
+
+        let tokyo = chrono_tz::Asia::Tokyo;
+        assert_eq!(entry.start_time_in(&tokyo).unwrap().to_rfc3339(), "2022-03-19T16:40:00+09:00");
+        assert_eq!(entry.end_time_in(&tokyo).unwrap().to_rfc3339(), "2022-03-19T18:24:00+09:00");
+    }
+
+    #[test]
+    fn company_anonymous_members() {
+        let company = Company::from_str("{Gary, +2}").unwrap();
+        let expected = Company { names: vec!["Gary".to_string()], anonymous: 2, solo: false };
+        assert_eq!(company, expected);
+        assert_eq!(company.anonymous(), 2);
+        assert_eq!(company.len(), 3);
+
+        let company = Company::from_str("{+1}").unwrap();
+        assert_eq!(company.iter().count(), 0);
+        assert_eq!(company.len(), 1);
+
+        let company = Company::from_str("{Gary, +two}");
+        assert!(company.is_err());
+    }
+
+    #[test]
+    fn company_parse_errors() {
+        assert_eq!("{Gary, Amim}".parse::<Company>().unwrap(), Company::from_str("{Gary, Amim}").unwrap());
+        assert_eq!("Gary, Amim".parse::<Company>(), Err(CompanyParseError::MissingBraces("Gary, Amim".to_string())));
+        assert_eq!("{Gary,,Amim}".parse::<Company>(), Err(CompanyParseError::EmptyName("{Gary,,Amim}".to_string())));
+        assert_eq!("{, Gary}".parse::<Company>(), Err(CompanyParseError::EmptyName("{, Gary}".to_string())));
+        assert_eq!("{Gary, +two}".parse::<Company>(), Err(CompanyParseError::InvalidAnonymousCount("+two".to_string())));
+
+        assert_eq!(Company::from_str("{Gary, +two}").unwrap_err(), "Invalid anonymous company count: \"+two\"");
+
+        // The inherent method still skips empty members
+        let expected = Company { names: vec!["Gary".to_string(), "Amim".to_string()], anonymous: 0, solo: false };
+        assert_eq!(Company::from_str("{Gary,,Amim}").unwrap(), expected);
+        assert_eq!(Company::from_str("{, Gary, Amim}").unwrap(), expected);
+        assert_eq!(Company::from_str_with_separator("{Gary;;Amim}", ';').unwrap(), expected);
+    }
+
+    #[test]
+    fn company_solo_marker() {
+        for marker in ["{solo}", "{ Solo }", "{-}"] {
+            assert_eq!(Company::from_str(marker).unwrap(), Company::solo(), "{:?}", marker);
+        }
+        assert_eq!(Company::from_tag("[with: solo]").unwrap(), Company::solo());
+
+        let solo = Company::solo();
+        assert!(solo.is_solo());
+        assert!(solo.is_empty());
+        assert!(!solo.contains("solo"));
+        assert_eq!(Company::from_str(&solo.to_string()).unwrap(), solo);
+
+        let unrecorded = Company::from_str("{}").unwrap();
+        assert!(!unrecorded.is_solo());
+        assert_ne!(unrecorded, solo);
+
+        let mut company = Company::solo();
+        company.merge(&Company::from_str("{Gary}").unwrap());
+        assert!(!company.is_solo());
+        assert!(company.contains("Gary"));
+    }
+
+    #[test]
+    fn company_with_separator() {
+        let expected = Company::from_str("{Gary, Amim}").unwrap();
+        assert_eq!(Company::from_str_with_separator("{Gary; Amim}", ';').unwrap(), expected);
+        assert_eq!(Company::from_str_with_separator("{Gary, Amim}", ',').unwrap(), expected);
+        assert_eq!(Company::from_str_with_separator("{Gary; +2;}", ';').unwrap(), Company { names: vec!["Gary".to_string()], anonymous: 2, solo: false });
+
+        // With another separator, commas are part of the name
+        assert_eq!(Company::from_str_with_separator("{Gary, Amim}", ';').unwrap().len(), 1);
+    }
+
+    #[test]
+    fn company_from_tag() {
+        let expected = Company::from_str("{Gary, Amim}").unwrap();
+        assert_eq!(Company::from_tag("[with: Gary, Amim]").unwrap(), expected);
+        assert_eq!(Company::from_tag("[with:Gary, Amim]").unwrap(), expected);
+        assert_eq!(Company::from_tag("[with: Gary, +2]").unwrap(), Company::from_str("{Gary, +2}").unwrap());
+
+        assert!(Company::from_tag("[Gary, Amim]").is_err());
+        assert!(Company::from_tag("{Gary, Amim}").is_err());
+    }
+
+    #[test]
+    fn company_display_uses_braces() {
+        let company = Company::from_tag("[with: Gary, Amim]").unwrap();
+        assert_eq!(company.to_string(), "{Gary,Amim}");
+        assert_eq!(Company::from_str(&company.to_string()).unwrap(), company);
+
+        assert_eq!(Company::from_str("{Gary, +2}").unwrap().to_string(), "{Gary,+2}");
+        assert_eq!(Company::from_str("{}").unwrap().to_string(), "{}");
+    }
+
+    #[test]
+    fn watch_entry_builder_without_end_is_open_ended() {
+        let start = DateTime::from_timestamp(0, 0).unwrap().naive_utc();
+
+        let entry = WatchEntry::builder()
+            .anime_id(1)
+            .start(start)
+            .episode(Episode::from("3").unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(entry, WatchEntry::open_ended(1, start, Episode::from("3").unwrap(), None));
+        assert_eq!(entry.end_time, None);
+    }
+
+    #[test]
+    fn split_at_midnight() {
+        let at = |datetime: &str| NaiveDateTime::parse_from_str(datetime, "%d/%m/%Y %H:%M:%S").unwrap();
+        let company = Some(Company::from_str("{Gary}").unwrap());
+
+        let entry = WatchEntry::new(1, at("10/02/2022 23:40:00"), at("11/02/2022 00:20:00"), Episode::from("12").unwrap(), company.clone());
+        let parts = entry.split_at_midnight();
+        assert_eq!(parts, vec![
+            WatchEntry::new(1, at("10/02/2022 23:40:00"), at("10/02/2022 23:59:59"), Episode::from("12").unwrap(), company.clone()),
+            WatchEntry::new(1, at("11/02/2022 00:00:00"), at("11/02/2022 00:20:00"), Episode::from("12").unwrap(), company.clone()),
+        ]);
+
+        let entry = WatchEntry::new(1, at("10/02/2022 23:40:00"), at("11/02/2022 00:00:00"), Episode::from("12").unwrap(), company.clone());
+        assert_eq!(entry.split_at_midnight(), vec![
+            WatchEntry::new(1, at("10/02/2022 23:40:00"), at("10/02/2022 23:59:59"), Episode::from("12").unwrap(), company.clone()),
+        ]);
+
+        let entry = WatchEntry::new(1, at("10/02/2022 22:40:00"), at("10/02/2022 23:20:00"), Episode::from("12").unwrap(), company);
+        assert_eq!(entry.split_at_midnight(), vec![entry]);
+    }
+
+    #[test]
+    fn same_session_ignores_annotations() {
+        let at = |datetime: &str| NaiveDateTime::parse_from_str(datetime, "%d/%m/%Y %H:%M:%S").unwrap();
+
+        let entry = WatchEntry::new(1, at("10/02/2022 22:00:00"), at("10/02/2022 22:30:00"), Episode::from("12").unwrap(), None);
+        let with_company = WatchEntry {
+            company: Some(Company::from_str("{Gary}").unwrap()),
+            session_tag: Some("rewatch".to_string()),
+            ..entry.clone()
+        };
+        assert!(entry.same_session(&with_company));
+        assert_ne!(entry, with_company);
+
+        let later = WatchEntry { end_time: Some(at("10/02/2022 22:31:00")), ..entry.clone() };
+        assert!(!entry.same_session(&later));
+        let other_episode = WatchEntry { episode: Episode::from("13").unwrap(), ..entry.clone() };
+        assert!(!entry.same_session(&other_episode));
+        let other_anime = WatchEntry { anime_id: 2, ..entry.clone() };
+        assert!(!entry.same_session(&other_anime));
+    }
+
+    #[test]
+    fn entries_and_companies_dedup_in_sets() {
+        let at = |datetime: &str| NaiveDateTime::parse_from_str(datetime, "%d/%m/%Y %H:%M:%S").unwrap();
+        let gary = Company::from_str("{Gary}").unwrap();
+
+        let entry = WatchEntry::new(1, at("10/02/2022 22:00:00"), at("10/02/2022 22:30:00"), Episode::from("12").unwrap(), Some(gary.clone()));
+        let with_tag = WatchEntry { session_tag: Some("rewatch".to_string()), ..entry.clone() };
+        let entries: std::collections::HashSet<WatchEntry> = [entry.clone(), with_tag, entry.clone()].into_iter().collect();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.contains(&entry));
+
+        let companies: std::collections::HashSet<Company> = [gary.clone(), Company::from_str("{Gary}").unwrap(), Company::solo()].into_iter().collect();
+        assert_eq!(companies.len(), 2);
+    }
+
+    #[test]
+    fn company_spacing_is_not_part_of_identity() {
+        let spellings = ["{Gary, Amim}", "{Gary,Amim}", "{ Gary ,  Amim }", "{Gary,Amim,}"];
+        let companies: std::collections::HashSet<Company> = spellings.iter().map(|spelling| Company::from_str(spelling).unwrap()).collect();
+        assert_eq!(companies.len(), 1);
+        assert_eq!(companies.iter().next().unwrap().to_string(), "{Gary,Amim}");
+    }
+}
@@ -0,0 +1,7 @@
+#![allow(dead_code, unused_variables)]
+
+pub mod parsing;
+pub mod elements;
+pub mod database;
+pub mod types;
+pub mod report;
@@ -1,5 +1,3 @@
-#![allow(dead_code, unused_variables)]
-
 // export const COMMENT_TOKEN = '//';
 // export const SHOW_TITLE_REG = makeGlobalReg(/^\s*([a-zA-Z0-9][^{[}\]]*)\:\s*$/g);
 // export const DATE_REG = makeGlobalReg(/^(\d{2}\/\d{2}\/\d{4})\s*$/g);
@@ -7,13 +5,181 @@
 // export const TAG_REG = makeGlobalReg(/^(?<!\[)\[([^=[\]0-9]+?)(?:\(([^)]+)\))?\](?!\])$/);
 // export const TAG_PARAM_REG = makeGlobalReg(/^([^=,0-9]+)=([^),]+)$/);
 
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::ExitCode;
+
+use rust_anime::database::simple_database::SimpleDatabase;
+use rust_anime::elements::Episode;
+use rust_anime::parsing::{FileParser, ParserConfig};
+use rust_anime::report::{builtin_reports, report_for_format};
+use rust_anime::types::{ParseDiagnostic, Severity};
+
+const USAGE: &str = "Usage: rust-anime validate <file>\n       rust-anime export --format <format> <file>\n       rust-anime search <query> <file>\n       rust-anime log --anime <title> --start <HH:MM> --end <HH:MM> --episode <episode> [--with <names>] <file>";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["validate", path] => validate(path),
+        ["export", "--format", format, path] => export(format, path),
+        ["search", query, path] => search(query, path),
+        ["log", options @ .., path] if !options.is_empty() => log(options, path),
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn read_log(path: &str) -> Result<String, ExitCode> {
+    std::fs::read_to_string(path).map_err(|e| {
+        eprintln!("Can't read {}: {}", path, e);
+        ExitCode::from(2)
+    })
+}
+
+/// Parses the whole log and reports every problem found, failing if there is any
+fn validate(path: &str) -> ExitCode {
+    let content = match read_log(path) {
+        Ok(content) => content,
+        Err(code) => return code,
+    };
+
+    let mut db = SimpleDatabase::new();
+    let mut parser = FileParser::new(ParserConfig { strict_unknown: true, ..ParserConfig::default() });
+    let errors = parser.parse_into_collecting(&content, &mut db);
+
+    let duplicates = db.find_duplicate_entries().into_iter()
+        .map(|entry| ParseDiagnostic::warning(format!("Duplicate entry: episode {} at {}", entry.episode, entry.start_time)));
+
+    let mut issues: Vec<ParseDiagnostic> = errors.into_iter().chain(parser.warnings().iter().cloned()).chain(duplicates).collect();
+    if issues.is_empty() {
+        let entries: usize = db.animes().map(|anime| anime.watch_entries().count()).sum();
+        println!("OK, {} entries, {} animes", entries, db.animes().count());
+        return ExitCode::SUCCESS;
+    }
+
+    // Issues without a line (found after parsing) go last
+    issues.sort_by_key(|issue| (issue.line.is_none(), issue.line));
+    for issue in &issues {
+        println!("{}", issue);
+    }
+
+    let count = |severity: Severity| issues.iter().filter(|issue| issue.severity == severity).count();
+    eprintln!("{} errors, {} warnings", count(Severity::Error), count(Severity::Warning));
+    ExitCode::FAILURE
+}
+
+/// Renders the whole log with the report registered for `format`
+fn export(format: &str, path: &str) -> ExitCode {
+    let Some(report) = report_for_format(format) else {
+        let formats: Vec<&str> = builtin_reports().iter().map(|report| report.format()).collect();
+        eprintln!("Unknown format \"{}\", expected one of: {}", format, formats.join(", "));
+        return ExitCode::from(2);
+    };
+    let db = match load_database(path) {
+        Ok(db) => db,
+        Err(code) => return code,
+    };
+
+    print!("{}", report.render(&db));
+    ExitCode::SUCCESS
+}
+
+/// Lists the animes matching `query`, best match first
+fn search(query: &str, path: &str) -> ExitCode {
+    let db = match load_database(path) {
+        Ok(db) => db,
+        Err(code) => return code,
+    };
+
+    for anime in db.search(query) {
+        println!("{}", anime.name());
+    }
+    ExitCode::SUCCESS
+}
+
+/// Appends a watch line under today's date, adding the date header if the log isn't on today yet
+fn log(options: &[&str], path: &str) -> ExitCode {
+    let mut values: HashMap<&str, &str> = HashMap::new();
+    for option in options.chunks(2) {
+        match option {
+            [name @ ("--anime" | "--start" | "--end" | "--episode" | "--with"), value] => {
+                values.insert(name, value);
+            },
+            _ => {
+                eprintln!("{}", USAGE);
+                return ExitCode::from(2);
+            },
+        }
+    }
+    let (Some(anime), Some(start), Some(end), Some(episode)) = (values.get("--anime"), values.get("--start"), values.get("--end"), values.get("--episode")) else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            eprintln!("Can't read {}: {}", path, e);
+            return ExitCode::from(2);
+        },
+    };
+
+    let mut parser = FileParser::new(ParserConfig::default());
+    if let Err(e) = parser.parse_into(&content, &mut SimpleDatabase::new()) {
+        eprintln!("{}", e);
+        return ExitCode::FAILURE;
+    }
+
+    let mut appended = String::new();
+    if !content.is_empty() && !content.ends_with('\n') {
+        appended.push('\n');
+    }
+    let today = chrono::Local::now().date_naive();
+    if parser.context().current_date() != Some(today) {
+        if !content.trim().is_empty() {
+            appended.push('\n');
+        }
+        appended += &format!("{}\n", today.format("%d/%m/%Y"));
+    }
+
+    let mut line = format!("{} - {} {}", start, end, Episode::from(episode).map_or_else(|_| episode.to_string(), |episode| episode.to_string()));
+    if let Some(names) = values.get("--with") {
+        line += &format!(" {{{}}}", names);
+    }
+    // Always repeating the title also ends any session tag the log was left in
+    appended += &format!("{}:\n{}\n", anime, line);
+
+    // The line is only written if the whole log, with it, still parses
+    let mut db = SimpleDatabase::new();
+    let strict = ParserConfig { strict_unknown: true, ..ParserConfig::default() };
+    if let Err(e) = FileParser::new(strict).parse_into(&(content + &appended), &mut db) {
+        eprintln!("{}", e);
+        return ExitCode::FAILURE;
+    }
 
-mod parsing;
-mod elements;
-mod database;
-mod types;
+    let written = std::fs::OpenOptions::new().create(true).append(true).open(path)
+        .and_then(|mut file| file.write_all(appended.as_bytes()));
+    if let Err(e) = written {
+        eprintln!("Can't write {}: {}", path, e);
+        return ExitCode::from(2);
+    }
 
+    println!("{}", line);
+    ExitCode::SUCCESS
+}
 
-fn main() {
+/// Reads and parses the log, reporting the first error
+fn load_database(path: &str) -> Result<SimpleDatabase, ExitCode> {
+    let content = read_log(path)?;
 
-}
\ No newline at end of file
+    let mut db = SimpleDatabase::new();
+    FileParser::new(ParserConfig::default()).parse_into(&content, &mut db).map_err(|e| {
+        eprintln!("{}", e);
+        ExitCode::FAILURE
+    })?;
+    Ok(db)
+}
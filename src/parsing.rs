@@ -1,35 +1,74 @@
-use chrono::{NaiveDate, NaiveTime, Datelike};
+use std::path::Path;
+use std::sync::LazyLock;
+
+use chrono::{Datelike, NaiveDate, NaiveTime, Duration, Weekday};
+use chrono_tz::Tz;
 use regex::{Regex};
 
-#[path = "./elements.rs"]
-mod elements;
-use elements::*;
+use crate::database::*;
+use crate::elements::*;
+use crate::types::*;
 
-#[path = "./types.rs"]
-mod types;
-use types::*;
+/// Lines of a log, past the UTF-8 BOM some editors start files with
+fn log_lines(content: &str) -> std::str::Lines<'_> {
+    content.strip_prefix('\u{FEFF}').unwrap_or(content).lines()
+}
 
-#[derive(Debug, PartialEq)]
-struct ParsingContext {
+fn is_blank_or_comment(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.is_empty() || trimmed.starts_with("//")
+}
+
+fn next_day(date: NaiveDate) -> Result<NaiveDate, ParseDiagnostic> {
+    date.succ_opt().ok_or_else(|| format!("Date {} has no following day", date).into())
+}
+
+/// What the parser knows from the lines read so far. To resume a parse later (see `FileParser::with_context`),
+/// keep a clone of it, or serialize it with the `serde` feature to store it between runs.
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParsingContext {
     current_date: Option<NaiveDate>,
     current_anime: Option<AnimeID>,
     last_watch_entry: Option<WatchEntry>,
+    /// When the last entry of the day ended, whatever its anime: unlike `last_watch_entry`,
+    /// kept across titles so a title logged after midnight still moves on to the next day
+    last_entry_time: Option<NaiveTime>,
     last_company: Option<Company>,
     // current_anime_tag
-    // current_session_tag
+    /// Tag from the last tag line, applied to entries until the next tag or title
+    current_session_tag: Option<String>,
     // current_episode_tag
 }
 
 impl ParsingContext {
+    /// How far back (in hours) a start time must jump from `last_entry_time`
+    /// to be read as "after midnight" instead of an overlapping entry on the same day
+    const MIDNIGHT_ROLLOVER_MIN_GAP_HOURS: i64 = 12;
+
     pub fn new() -> Self {
         Self {
             current_date: None,
             current_anime: None,
             last_watch_entry: None,
+            last_entry_time: None,
             last_company: None,
+            current_session_tag: None,
         }
     }
 
+    /// Whether an entry starting at `start_time` started on the day after the last entry ended.
+    /// Going past midnight makes the clock jump far back (23:30 to 00:15 is 23h15m), while
+    /// overlapping or re-logged sessions only step back a little (21:30 after 22:00).
+    fn starts_after_midnight(&self, start_time: NaiveTime) -> bool {
+        self.last_entry_time.is_some_and(|last_time| last_time.signed_duration_since(start_time) > Duration::hours(Self::MIDNIGHT_ROLLOVER_MIN_GAP_HOURS))
+    }
+
+    /// Date the next line is logged on, moved forward by entries crossing midnight
+    pub fn current_date(&self) -> Option<NaiveDate> {
+        self.current_date
+    }
+
     pub fn notify_new_current_date(&mut self, date: NaiveDate) -> Result<(), String> {
         if let Some(current_date) = self.current_date {
             if current_date >= date {
@@ -40,6 +79,8 @@ impl ParsingContext {
         self.current_date = Some(date);
         self.current_anime = None;
         self.last_watch_entry = None;
+        self.last_entry_time = None;
+        self.current_session_tag = None;
 
         Ok(())
     }
@@ -47,19 +88,24 @@ impl ParsingContext {
     pub fn notify_new_current_anime(&mut self, anime_id: AnimeID) -> Result<(), String> {
         self.current_anime = Some(anime_id);
         self.last_watch_entry = None;
+        self.current_session_tag = None;
+        Ok(())
+    }
+
+    pub fn notify_new_session_tag(&mut self, tag: Option<String>) -> Result<(), String> {
+        self.current_session_tag = tag;
         Ok(())
     }
 
     pub fn notify_new_watch_entry(&mut self, entry: WatchEntry) -> Result<(), String> {
-        self.last_watch_entry = match self.last_watch_entry {
-            Some(ref last_entry) => {
-                assert_eq!(last_entry.anime_id, entry.anime_id, "Anime ID mismatch");
-                Some(entry)
-            },
-            None => {
-                Some(entry)
-            },
-        };
+        if let Some(ref last_entry) = self.last_watch_entry {
+            if last_entry.anime_id != entry.anime_id {
+                return Err(format!("Watch entry for anime {} follows one for anime {} without a new title", entry.anime_id, last_entry.anime_id));
+            }
+        }
+
+        self.last_entry_time = Some(entry.end_time.unwrap_or(entry.start_time).time());
+        self.last_watch_entry = Some(entry);
 
         Ok(())
     }
@@ -71,112 +117,310 @@ impl ParsingContext {
     }
 }
 
-struct Database {
+/// What every line of a log became, from `FileParser::parse_detailed`; records carry their line number
+#[derive(Debug, PartialEq, Default)]
+pub struct ParseReport {
+    pub entries: Vec<(usize, WatchEntry)>,
+    pub titles: Vec<(usize, String)>,
+    pub dates: Vec<(usize, NaiveDate)>,
+    /// Session tag lines, e.g. `[rewatch]`
+    pub tags: Vec<(usize, String)>,
+    /// Lines that weren't used: unrecognized, or with an error (in `diagnostics`). Blank lines and comments aren't listed.
+    pub skipped_lines: Vec<(usize, String)>,
+    /// Errors and warnings, by line
+    pub diagnostics: Vec<ParseDiagnostic>,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct Database {
     animes: Vec<String>,
     watch_entries: Vec<WatchEntry>,
+    /// Timezone the naive entry times were logged in, if known
+    timezone: Option<Tz>,
+}
+
+impl Database {
+    /// Timezone the naive entry times were logged in, if known
+    pub fn timezone(&self) -> Option<Tz> {
+        self.timezone
+    }
+}
+
+/// Destination for the titles and entries read by `FileParser`
+pub trait ParseTarget {
+    fn anime_id_for_title(&mut self, title: &str) -> Result<AnimeID, ParseDiagnostic>;
+    fn push_watch_entry(&mut self, entry: WatchEntry) -> Result<(), ParseDiagnostic>;
+
+    /// Called with `ParserConfig::timezone` before parsing, when one is set
+    fn set_timezone(&mut self, _timezone: Tz) {}
+}
+
+impl ParseTarget for Database {
+    fn anime_id_for_title(&mut self, title: &str) -> Result<AnimeID, ParseDiagnostic> {
+        match self.animes.iter().position(|anime| anime == title) {
+            Some(anime_id) => Ok(anime_id),
+            None => {
+                self.animes.push(title.to_string());
+                Ok(self.animes.len() - 1)
+            }
+        }
+    }
+
+    fn push_watch_entry(&mut self, entry: WatchEntry) -> Result<(), ParseDiagnostic> {
+        self.watch_entries.push(entry);
+        Ok(())
+    }
+
+    fn set_timezone(&mut self, timezone: Tz) {
+        self.timezone = Some(timezone);
+    }
+}
+
+/// Titles already in the database are reused, new ones are added
+impl<DB: AnimeDB> ParseTarget for DB {
+    fn anime_id_for_title(&mut self, title: &str) -> Result<AnimeID, ParseDiagnostic> {
+        if let Some(anime) = self.find_anime_by_name(title) {
+            return Ok(anime.id());
+        }
+
+        self.add_new_anime(title).map_err(|e| ParseDiagnostic::error(e.to_string()))
+    }
+
+    fn push_watch_entry(&mut self, entry: WatchEntry) -> Result<(), ParseDiagnostic> {
+        self.add_watch_entry(entry).map_err(|e| ParseDiagnostic::error(e.to_string()))
+    }
+
+    fn set_timezone(&mut self, timezone: Tz) {
+        AnimeDB::set_timezone(self, Some(timezone));
+    }
 }
 
 trait LineParser<T> {
     fn parse(&mut self, line: &str) -> Result<T, ParseDiagnostic>;
 }
 
+// An optional weekday prefix (`Sat 19/03/2022`) must match the date
+static DATE_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(?:([A-Za-z]{3})\s+)?(\d{2}/\d{2}/\d{4})\s*(?://.*)?\s*$").unwrap());
+
+/// Cheap check for a leading "DD/", after the weekday if there is one
+fn looks_like_date(line: &str) -> bool {
+    let line = line.trim_start();
+    let date = match line.as_bytes() {
+        [a, b, c, b' ' | b'\t', ..] if [a, b, c].iter().all(|c| c.is_ascii_alphabetic()) => line[3..].trim_start(),
+        _ => line,
+    };
+    matches!(date.as_bytes(), [d, m, b'/', ..] if d.is_ascii_digit() && m.is_ascii_digit())
+}
+
 #[derive(Debug, PartialEq)]
 struct DateLineParser;
 impl LineParser<NaiveDate> for DateLineParser {
     fn parse(&mut self, line: &str) -> Result<NaiveDate, ParseDiagnostic> {
-        let re = Regex::new(r"^\s*(\d{2}/\d{2}/\d{4})\s*(?://.*)?\s*$").unwrap();
-        let caps = re.captures(line).ok_or_else(|| format!("Date parse error: \"{}\"", line))?;
-        let date_str = match caps.get(1) {
+        if !looks_like_date(line) {
+            return Err(format!("Date parse error: \"{}\"", line).into());
+        }
+        let caps = DATE_REGEX.captures(line).ok_or_else(|| format!("Date parse error: \"{}\"", line))?;
+        let date_str = match caps.get(2) {
             Some(s) => s.as_str(),
-            None => return Err(format!("Date not found on line: {}", line))
+            None => return Err(format!("Date not found on line: {}", line).into())
         };
 
-        match NaiveDate::parse_from_str(date_str, "%d/%m/%Y") {
-            Ok(date) => Ok(date),
-            Err(e) => Err(format!("Date parse error: {}", e))
+        let date = NaiveDate::parse_from_str(date_str, "%d/%m/%Y").map_err(|e| format!("Date parse error: {}", e))?;
+        if let Some(weekday) = caps.get(1) {
+            let weekday = weekday.as_str().parse::<Weekday>().map_err(|_| format!("Unknown weekday \"{}\"", weekday.as_str()))?;
+            if weekday != date.weekday() {
+                return Err(format!("{} is a {}, not a {}", date_str, date.weekday(), weekday).into());
+            }
         }
+
+        Ok(date)
     }
 }
 
+// Closed entries read "22:00 - 23:00 05"; an entry without an end time is open-ended,
+// with ("22:00 - 05") or without ("22:00 05") the dash. The end group only takes HH:MM,
+// so a bare number after the dash is always the episode.
+// An episode of `+` continues from the previous entry (`++` skips one).
+// An end time of `07:00+1` ends that many days after the start, whatever the clock says.
+// Company is `{Gary, Amim}`, or `[with: Gary, Amim]` in older logs.
+static WATCH_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(?:(\d{2}/\d{2}/\d{4})\s+)?([0-9]{2}:[0-9]{2}(?:\s*[AaPp][Mm]\b)?)(?:\s*-\s*([0-9]{2}:[0-9]{2}(?:\s*[AaPp][Mm]\b)?)?(?:\+([0-9]+))?)?\s+(\[\s*[0-9][^\]]*\]|[0-9]+\s*->(?:\s*[0-9]+)?|[0-9][0-9.]{1,}|[A-Za-z][A-Za-z0-9]*|\+\+?|--)?\s*(\{[^{}]*\}|\[\s*with:[^\[\]]*\])?((?:\s*\[[^\[\]]+\])*)\s*(?://.*)?$").unwrap());
+static ENTRY_TAG_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[([^\[\]]+)\]").unwrap());
+
+/// `05 16:40 - 18:24 {Gary}`: the episode before the times, then whatever a watch line allows after its episode
+static EPISODE_FIRST_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\s*)(\[\s*[0-9][^\]]*\]|[0-9]+\s*->(?:\s*[0-9]+)?|[0-9][0-9.]{1,}|[A-Za-z][A-Za-z0-9]*|\+\+?|--)\s+([0-9]{2}:[0-9]{2}(?:\s*[AaPp][Mm]\b)?(?:\s*-\s*(?:[0-9]{2}:[0-9]{2}(?:\s*[AaPp][Mm]\b)?)?(?:\+[0-9]+)?)?)(.*)$").unwrap());
+
+/// Rewrites an episode-first watch line into the usual times-first order. A leading date can't be taken
+/// for an episode, as its first number is followed by '/' rather than whitespace.
+fn episode_first_to_standard(line: &str) -> Option<String> {
+    if looks_like_watch(line) || !line.contains(':') {
+        return None;
+    }
+    let caps = EPISODE_FIRST_REGEX.captures(line)?;
+    Some(format!("{}{} {}{}", &caps[1], &caps[3], &caps[2], &caps[4]))
+}
+
+/// Fast path: watch lines start with "HH:", possibly indented or after an inline "DD/MM/YYYY "
+fn looks_like_watch(line: &str) -> bool {
+    let line = line.trim_start();
+    let time = match line.as_bytes() {
+        [_, _, b'/', _, _, b'/', _, _, _, _, b' ' | b'\t', ..] => line[10..].trim_start(),
+        _ => line,
+    };
+    matches!(time.as_bytes(), [h, m, b':', ..] if h.is_ascii_digit() && m.is_ascii_digit())
+}
+
+/// `HH:MM`, with an AM/PM suffix in the 12-hour format
+fn parse_time(time: &str, format: TimeFormat) -> Result<NaiveTime, String> {
+    // The regex guarantees an ASCII "HH:MM" first
+    let (clock, period) = time.split_at(5);
+    let period = period.trim().to_ascii_uppercase();
+    let parsed = match (format, period.is_empty()) {
+        (TimeFormat::TwentyFourHour, true) => NaiveTime::parse_from_str(clock, "%H:%M"),
+        (TimeFormat::TwelveHour, false) => NaiveTime::parse_from_str(&format!("{} {}", clock, period), "%I:%M %p"),
+        (TimeFormat::TwentyFourHour, false) => return Err(format!("\"{}\" is a 12-hour time, but the time format is 24-hour", time)),
+        (TimeFormat::TwelveHour, true) => return Err(format!("\"{}\" is missing AM or PM", time)),
+    };
+    parsed.map_err(|e| e.to_string())
+}
+
 #[derive(Debug, PartialEq)]
 struct WatchLineParser<'a> {
     context: &'a mut ParsingContext,
+    config: &'a ParserConfig,
 }
 
 impl LineParser<WatchEntry> for WatchLineParser<'_> {
     fn parse(&mut self, line: &str) -> Result<WatchEntry, ParseDiagnostic> {
-        let mut current_date = self.context.current_date.ok_or_else(|| "No current date!".to_string())?;
-        let current_anime = self.context.current_anime.ok_or_else(|| "No current anime!".to_string())?;
-
-        let re = Regex::new(r"^([0-9]{2}:[0-9]{2})\s*-\s*([0-9]{2}:[0-9]{2})?\s+([0-9][0-9.]{1,}|--)?\s*(\{.*\})?\s*$").unwrap();
-        let groups = re.captures(line).ok_or_else(|| format!("Line doesn't match regex: {}", line))?;
+        let reordered = episode_first_to_standard(line);
+        let line = reordered.as_deref().unwrap_or(line);
+        if !looks_like_watch(line) {
+            return Err(format!("Line doesn't match regex: {}", line).into());
+        }
+        let groups = WATCH_REGEX.captures(line).ok_or_else(|| format!("Line doesn't match regex: {}", line))?;
+
+        let no_anime = || "No current anime: did you forget a title line ending with ':'?".to_string();
+
+        // An inline date must match the current date, or move on to a later day like a date line would.
+        // The context only moves to the new day once the entry turns out valid, at the end.
+        let inline_date = groups.get(1).map(|date| NaiveDate::parse_from_str(date.as_str(), "%d/%m/%Y").map_err(|e| format!("Invalid inline date: {}", e))).transpose()?;
+        let mut current_date = self.context.current_date;
+        if let Some(inline_date) = inline_date {
+            match current_date {
+                Some(current_date) if inline_date < current_date => {
+                    return Err(format!("Inline date {} is before the current date {}", inline_date.format("%d/%m/%Y"), current_date.format("%d/%m/%Y")).into());
+                },
+                _ => current_date = Some(inline_date),
+            }
+        }
 
-        let start_time = groups.get(1).ok_or_else(|| "No start time!".to_string())?.as_str();
-        let end_time = groups.get(2).ok_or_else(|| "No end time!".to_string())?.as_str();
-        let episode = groups.get(3).ok_or_else(|| "No episode number!".to_string())?.as_str();
-        let company_match = groups.get(4);
+        let current_anime = self.context.current_anime.ok_or_else(no_anime)?;
+        let mut current_date = current_date.ok_or_else(|| "No current date!".to_string())?;
+        // A new day forgets the previous entry, as a date line would
+        let new_day = self.context.current_date != Some(current_date);
+        let last_watch_entry = self.context.last_watch_entry.as_ref().filter(|_| !new_day);
+
+        let start_time = groups.get(2).ok_or_else(|| "No start time!".to_string())?.as_str();
+        let end_time = groups.get(3).map(|end_time| end_time.as_str());
+        let day_offset = groups.get(4).map(|days| days.as_str().parse::<i64>().map_err(|e| format!("Invalid day offset: {}", e))).transpose()?;
+        let episode = groups.get(5).ok_or_else(|| "No episode number!".to_string())?.as_str();
+        let company_match = groups.get(6);
+        let tags: Vec<String> = groups.get(7)
+            .map(|tags| ENTRY_TAG_REGEX.captures_iter(tags.as_str()).map(|caps| caps[1].trim().to_string()).collect())
+            .unwrap_or_default();
 
         //Convert times to NaiveTime
-        let start_time = NaiveTime::parse_from_str(start_time, "%H:%M").map_err(|e| format!("Invalid start time: {}", e))?;
-        let end_time = NaiveTime::parse_from_str(end_time, "%H:%M").map_err(|e| format!("Invalid end time: {}", e))?; 
+        let start_time = parse_time(start_time, self.config.time_format).map_err(|e| format!("Invalid start time: {}", e))?;
+        let end_time = end_time.map(|end_time| parse_time(end_time, self.config.time_format).map_err(|e| format!("Invalid end time: {}", e))).transpose()?;
         
+        // An end before the start means the session went past midnight, unless a day offset says otherwise
+        if let Some(end_time) = end_time {
+            let mut session = end_time.signed_duration_since(start_time);
+            match day_offset {
+                Some(days) => session += Duration::try_days(days).ok_or_else(|| format!("Day offset +{} is out of range", days))?,
+                None if session < Duration::zero() => session += Duration::days(1),
+                None => {},
+            }
+            if session >= Duration::hours(self.config.max_session_hours) {
+                return Err(format!("Session of {}h{:02}m reaches the {}h maximum (typo in the times?)", session.num_hours(), session.num_minutes() % 60, self.config.max_session_hours).into());
+            }
+        }
+
+        // `+` continues from the previous entry of this anime on the same day
+        let episode = match episode.strip_prefix('+') {
+            Some(extra) => {
+                let last_episode = last_watch_entry.map(|last_entry| &last_entry.episode)
+                    .ok_or_else(|| format!("Episode \"{}\" needs a previous entry of the same anime", episode))?;
+                let step = 1 + extra.len() as i32;
+                // Seasonal episodes stay in their season; ranges and lists continue from their end
+                last_episode.succ_n(step)
+                    .or_else(|| last_episode.end().and_then(|end| end.checked_add(step)).map(Episode::Number))
+                    .ok_or_else(|| format!("Episode \"{}\" has no episode number to follow {}", episode, last_episode))?
+            },
+            None => Episode::from(episode).map_err(|e| format!("Invalid episode: {}", e))?,
+        };
+
         //TODO: check if trying to add an episode that is less than the last one
         //TODO: accept tags for out-of-order entries
 
         let anime_id = self.context.current_anime.ok_or("No current anime in context!".to_string())?;
+        let session_tag = self.context.current_session_tag.clone();
 
         //Special case for midnight
         let (mut start_date, mut end_date) = (current_date, current_date);
         {
-            // Start after midnight with previous watch entry on yesterday
-            if let Some(ref last_entry) = self.context.last_watch_entry {
-                if last_entry.end_time.time() > start_time {
-                    start_date = current_date.succ();
-                    end_date = start_date;
-                    current_date = current_date.succ();
-                    assert_eq!(last_entry.anime_id, anime_id, "Anime ID mismatch");
-
-                    self.context.notify_new_current_date(current_date)?;
-                    self.context.notify_new_current_anime(anime_id)?;
-                    //TODO: instead of re-adding all context after setting date, 
-                    //TODO: set date without resetting old context
-                }
+            // Start after midnight with previous watch entry (of any anime) on yesterday
+            if !new_day && self.context.starts_after_midnight(start_time) {
+                start_date = next_day(current_date)?;
+                end_date = start_date;
+                current_date = next_day(current_date)?;
             }
             
+            if let Some(days) = day_offset.filter(|days| *days > 0) {
+                end_date = start_date.checked_add_days(chrono::Days::new(days as u64)).ok_or_else(|| format!("Date {} plus {} days is out of range", start_date, days))?;
+                current_date = end_date;
+            }
+
             //Start before midnight and end after midnight
-            if end_time < start_time {
+            if day_offset.is_none() && end_time.is_some_and(|end_time| end_time < start_time) {
                 start_date = current_date;
-                end_date = current_date.succ();
-                current_date = current_date.succ();
-
-                self.context.notify_new_current_date(current_date)?;
-                self.context.notify_new_current_anime(anime_id)?;
-                //TODO: instead of re-adding all context after setting date, 
-                //TODO: set date without resetting old context
+                end_date = next_day(current_date)?;
+                current_date = next_day(current_date)?;
             }
 
         
         }
-        drop(current_date);
 
         //Account for current date in start and end times
-        let start_time = NaiveDate::from_ymd(start_date.year(), start_date.month(), start_date.day()).and_time(start_time);
-        let end_time = NaiveDate::from_ymd(end_date.year(), end_date.month(), end_date.day()).and_time(end_time);
-
-        let episode = Episode::from(episode).map_err(|e| format!("Invalid episode: {}", e))?;
+        let start_time = start_date.and_time(start_time);
+        let end_time = end_time.map(|end_time| end_date.and_time(end_time));
 
         let company = match company_match {
-            Some(company) => Some(Company::from_str(company.as_str())?),
+            Some(company) if company.as_str().starts_with('[') => Some(Company::from_tag_with_separator(company.as_str(), self.config.company_separator)?),
+            Some(company) => Some(Company::from_str_with_separator(company.as_str(), self.config.company_separator)?),
             None => None,
         };
 
-        let watch_entry = WatchEntry::new(
-            current_anime,
-            start_time,
-            end_time,
-            episode,
-            company
-        );
+        let mut watch_entry = match end_time {
+            Some(end_time) if end_time == start_time && self.config.allow_zero_duration => WatchEntry::new(current_anime, start_time, end_time, episode, company),
+            Some(end_time) => WatchEntry::try_new(
+                current_anime,
+                start_time,
+                end_time,
+                episode,
+                company
+            )?,
+            None => WatchEntry::open_ended(current_anime, start_time, episode, company),
+        };
+        // Only now that the entry is valid does the context move on to its day
+        if self.context.current_date != Some(current_date) {
+            //TODO: set date without resetting old context instead of re-adding it
+            self.context.notify_new_current_date(current_date)?;
+            self.context.notify_new_current_anime(anime_id)?;
+            self.context.notify_new_session_tag(session_tag.clone())?;
+        }
+
+        watch_entry.session_tag = session_tag;
+        watch_entry.tags = tags;
 
         self.context.notify_new_watch_entry(watch_entry.clone())?;
         
@@ -184,29 +428,442 @@ impl LineParser<WatchEntry> for WatchLineParser<'_> {
     }
 }
 
+// Any leading character but whitespace and brackets, so titles may start with a quote or a kanji
+static TITLE_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*([^\s\[\]\{\}][^\[\]\{\}]*):\s*(?://.*)?$").unwrap());
+
+/// Whether `line` starts with a time (`HH:MM`) or a date (`DD/MM/YYYY`), which are never titles
+fn starts_with_time_or_date(line: &str) -> bool {
+    let digit = |c: &u8| c.is_ascii_digit();
+    match line.trim_start().as_bytes() {
+        [h1, h2, b':', m1, m2, ..] => [h1, h2, m1, m2].into_iter().all(digit),
+        [d1, d2, b'/', m1, m2, b'/', y1, y2, y3, y4, ..] => [d1, d2, m1, m2, y1, y2, y3, y4].into_iter().all(digit),
+        _ => false,
+    }
+}
+
 #[derive(Debug, PartialEq)]
 struct TitleLineParser;
 
 impl LineParser<String> for TitleLineParser {
     fn parse(&mut self, line: &str) -> Result<String, ParseDiagnostic> {
-        let re = Regex::new(r"^\s*([a-zA-Z0-9][^\[\]\{\}]*):\s*(?://.*)?$").unwrap();
-        if !re.is_match(line) {
-            return Err(format!("Line doesn't match regex: \"{}\" instead of r\"^\\s*([a-zA-Z0-9][^{{[}}\\]]*):\\s*$\"", line));
-        }
-
-        let caps = re.captures(line).ok_or_else(|| format!("No matches found (missing semicolon?): \"{}\"", line))?;
+        // Fast path: titles contain a colon and don't start with a time or date
+        let title_like = line.contains(':') && !starts_with_time_or_date(line);
+        let caps = title_like.then(|| TITLE_REGEX.captures(line)).flatten().ok_or_else(|| format!("Line doesn't match regex: \"{}\" instead of r\"^\\s*([^\\s{{}}[\\]][^{{}}[\\]]*):\\s*$\"", line))?;
         let anime_title = caps.get(1).ok_or_else(|| format!("Can't match anime title (missing semicolon?): \"{}\"", line))?.as_str();
         
         Ok(anime_title.to_string())
     }
 }
 
+static TAG_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*\[([^=\[\]0-9][^=\[\]]*?(?:\([^)]+\))?)\]\s*(?://.*)?$").unwrap());
+
+#[derive(Debug, PartialEq)]
+struct TagLineParser;
+
+impl LineParser<String> for TagLineParser {
+    fn parse(&mut self, line: &str) -> Result<String, ParseDiagnostic> {
+        // Fast path: tags start with '['
+        if !line.trim_start().starts_with('[') {
+            return Err(format!("Line doesn't match tag regex: \"{}\"", line).into());
+        }
+        let caps = TAG_REGEX.captures(line).ok_or_else(|| format!("Line doesn't match tag regex: \"{}\"", line))?;
+        let tag = caps.get(1).ok_or_else(|| format!("Can't match tag: \"{}\"", line))?.as_str();
+
+        Ok(tag.trim().to_string())
+    }
+}
+
+/// What a single log line turned out to be
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParsedLine {
+    Date(NaiveDate),
+    Title(String),
+    Watch(WatchEntry),
+    Tag(String),
+}
+
+/// Object-safe counterpart of `LineParser`, so parsers for different line kinds can share a collection
+pub trait DynLineParser {
+    fn parse_line(&mut self, line: &str, context: &mut ParsingContext, config: &ParserConfig) -> Result<ParsedLine, ParseDiagnostic>;
+
+    /// Whether `line` is of this parser's kind even when it fails to parse,
+    /// so `FileParser` reports this parser's error rather than an unrecognized line
+    fn claims(&self, _line: &str) -> bool {
+        false
+    }
+}
+
+impl DynLineParser for DateLineParser {
+    fn parse_line(&mut self, line: &str, _: &mut ParsingContext, _: &ParserConfig) -> Result<ParsedLine, ParseDiagnostic> {
+        self.parse(line).map(ParsedLine::Date)
+    }
+
+    fn claims(&self, line: &str) -> bool {
+        looks_like_date(line) && DATE_REGEX.is_match(line)
+    }
+}
+
+impl DynLineParser for TitleLineParser {
+    fn parse_line(&mut self, line: &str, _: &mut ParsingContext, _: &ParserConfig) -> Result<ParsedLine, ParseDiagnostic> {
+        self.parse(line).map(ParsedLine::Title)
+    }
+}
+
+impl DynLineParser for TagLineParser {
+    fn parse_line(&mut self, line: &str, _: &mut ParsingContext, _: &ParserConfig) -> Result<ParsedLine, ParseDiagnostic> {
+        self.parse(line).map(ParsedLine::Tag)
+    }
+}
+
+/// Builds a `WatchLineParser` over whichever context it's handed
+#[derive(Debug, PartialEq)]
+struct DynWatchLineParser;
+
+impl DynLineParser for DynWatchLineParser {
+    fn parse_line(&mut self, line: &str, context: &mut ParsingContext, config: &ParserConfig) -> Result<ParsedLine, ParseDiagnostic> {
+        WatchLineParser{context, config}.parse(line).map(ParsedLine::Watch)
+    }
+
+    fn claims(&self, line: &str) -> bool {
+        let reordered = episode_first_to_standard(line);
+        let line = reordered.as_deref().unwrap_or(line);
+        looks_like_watch(line) && WATCH_REGEX.is_match(line)
+    }
+}
+
+/// Parsers for every line kind, in the order `FileParser` tries them
+fn default_line_parsers() -> Vec<Box<dyn DynLineParser>> {
+    vec![
+        Box::new(DateLineParser),
+        Box::new(TagLineParser),
+        Box::new(DynWatchLineParser),
+        Box::new(TitleLineParser),
+    ]
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// `16:40`
+    #[default]
+    TwentyFourHour,
+    /// `04:40 PM`
+    TwelveHour,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParserConfig {
+    /// Fail on lines that are not a date, title or watch entry instead of skipping them
+    pub strict_unknown: bool,
+    /// Timezone the file was logged in; times stay naive when unset
+    pub timezone: Option<Tz>,
+    /// Sessions this long or longer are rejected as typos, midnight crossings and day offsets included.
+    /// A clock-only line never reaches 24 hours, so the default only catches day offsets (`23:00 - 00:00+1`);
+    /// lower it to also refuse rollovers such as `03:00 - 02:00`, which is more likely a slip than a 23-hour session.
+    pub max_session_hours: i64,
+    /// Separates company members, as in `{Gary, Amim}` or `{Gary; Amim}`
+    pub company_separator: char,
+    /// Keep going past lines with errors in `parse_into`, collecting them in `errors()`
+    pub skip_errors: bool,
+    /// With `skip_errors`, also skip every line after an error up to the next date line,
+    /// so the rest of a broken block isn't attributed to the wrong anime
+    pub resync_on_date: bool,
+    /// Keep entries like `22:00 - 22:00` as zero-length markers instead of rejecting them
+    pub allow_zero_duration: bool,
+    /// How watch line times are written
+    pub time_format: TimeFormat,
+    /// Shorter sessions are warned about as likely typos, unless they are an OP or ED
+    pub min_session_minutes: i64,
+    /// Keep each watch line as written in its entry's `source_line`
+    pub keep_source_lines: bool,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self {
+            strict_unknown: false,
+            timezone: None,
+            max_session_hours: 24,
+            company_separator: ',',
+            skip_errors: false,
+            resync_on_date: false,
+            allow_zero_duration: false,
+            time_format: TimeFormat::default(),
+            min_session_minutes: 3,
+            keep_source_lines: false,
+        }
+    }
+}
+
+pub struct FileParser {
+    config: ParserConfig,
+    context: ParsingContext,
+    parsers: Vec<Box<dyn DynLineParser>>,
+    warnings: Vec<ParseDiagnostic>,
+    /// Errors skipped over under `skip_errors`
+    errors: Vec<ParseDiagnostic>,
+}
+
+impl FileParser {
+    pub fn new(config: ParserConfig) -> Self {
+        Self {
+            config,
+            context: ParsingContext::new(),
+            parsers: default_line_parsers(),
+            warnings: vec![],
+            errors: vec![],
+        }
+    }
+
+    /// Parser that resumes from a previously saved context, e.g. to parse only lines appended to a log
+    pub fn with_context(config: ParserConfig, context: ParsingContext) -> Self {
+        Self {
+            context,
+            ..Self::new(config)
+        }
+    }
+
+    pub fn context(&self) -> &ParsingContext {
+        &self.context
+    }
+
+    pub fn warnings(&self) -> &[ParseDiagnostic] {
+        &self.warnings
+    }
+
+    pub fn errors(&self) -> &[ParseDiagnostic] {
+        &self.errors
+    }
+
+    pub fn parse(&mut self, content: &str) -> Result<Database, ParseDiagnostic> {
+        let mut database = Database::default();
+
+        self.parse_into(content, &mut database)?;
+        Ok(database)
+    }
+
+    /// Parses `content` straight into an anime database, creating animes for unseen titles
+    pub fn parse_into<T: ParseTarget>(&mut self, content: &str, target: &mut T) -> Result<(), ParseDiagnostic> {
+        if let Some(timezone) = self.config.timezone {
+            target.set_timezone(timezone);
+        }
+
+        // Error that started the block being skipped under `resync_on_date`, and the last line skipped
+        let mut skipping: Option<(ParseDiagnostic, usize)> = None;
+        for (line_idx, line) in log_lines(content).enumerate() {
+            let line_number = line_idx + 1;
+            if let Some((error, last_line)) = skipping.as_mut() {
+                if DateLineParser.parse(line.trim()).is_err() {
+                    *last_line = line_number;
+                    continue;
+                }
+                self.errors.push(Self::skipped_block(error, *last_line));
+                skipping = None;
+            }
+
+            if let Err(e) = self.parse_line(line_number, line, target) {
+                let e = e.at_line(line_number);
+                if !self.config.skip_errors {
+                    return Err(e);
+                }
+                if self.config.resync_on_date {
+                    skipping = Some((e, line_number));
+                } else {
+                    self.errors.push(e);
+                }
+            }
+        }
+
+        if let Some((error, last_line)) = skipping {
+            self.errors.push(Self::skipped_block(&error, last_line));
+        }
+        Ok(())
+    }
+
+    /// Single diagnostic for every line skipped after `error`, up to `last_line`
+    fn skipped_block(error: &ParseDiagnostic, last_line: usize) -> ParseDiagnostic {
+        let first_line = error.line.unwrap_or(last_line);
+        ParseDiagnostic::error(format!("Skipped lines {}-{} up to the next date: {}", first_line, last_line, error.message)).at_line(first_line)
+    }
+
+    /// Parses every file in `dir`, in file name order, as one log: dates must keep moving forward from one
+    /// file to the next (e.g. `2021.txt` then `2022.txt`). Diagnostics are tagged with their file name.
+    pub fn parse_dir<T: ParseTarget>(&mut self, dir: impl AsRef<Path>, target: &mut T) -> Result<(), ParseDiagnostic> {
+        let dir = dir.as_ref();
+        let read_error = |path: &Path, e: std::io::Error| ParseDiagnostic::error(format!("Can't read {}: {}", path.display(), e));
+
+        let mut paths = vec![];
+        for entry in std::fs::read_dir(dir).map_err(|e| read_error(dir, e))? {
+            let path = entry.map_err(|e| read_error(dir, e))?.path();
+            if path.is_file() {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut files = vec![];
+        for path in paths {
+            let content = std::fs::read_to_string(&path).map_err(|e| read_error(&path, e))?;
+            let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            files.push((name, content));
+        }
+        self.parse_files(files.iter().map(|(name, content)| (name.as_str(), content.as_str())), target)
+    }
+
+    /// Like `parse_into` over several named files in turn, carrying the context from one to the next
+    pub fn parse_files<'a, T: ParseTarget>(&mut self, files: impl IntoIterator<Item = (&'a str, &'a str)>, target: &mut T) -> Result<(), ParseDiagnostic> {
+        for (name, content) in files {
+            let (warnings, errors) = (self.warnings.len(), self.errors.len());
+            let result = self.parse_into(content, target);
+            for diagnostic in self.warnings[warnings..].iter_mut().chain(&mut self.errors[errors..]) {
+                diagnostic.file = Some(name.to_string());
+            }
+            result.map_err(|e| e.in_file(name))?;
+        }
+
+        Ok(())
+    }
+
+    /// Parses `content`, keeping going after bad lines, and reports what each line became
+    pub fn parse_detailed(&mut self, content: &str) -> ParseReport {
+        let mut database = Database::default();
+        let warnings = self.warnings.len();
+
+        let mut report = ParseReport::default();
+        for (line_idx, line) in log_lines(content).enumerate() {
+            let line_number = line_idx + 1;
+            match self.parse_line(line_number, line, &mut database) {
+                Ok(Some(ParsedLine::Date(date))) => report.dates.push((line_number, date)),
+                Ok(Some(ParsedLine::Title(title))) => report.titles.push((line_number, title)),
+                Ok(Some(ParsedLine::Tag(tag))) => report.tags.push((line_number, tag)),
+                Ok(Some(ParsedLine::Watch(entry))) => report.entries.push((line_number, entry)),
+                Ok(None) if is_blank_or_comment(line) => {},
+                Ok(None) => report.skipped_lines.push((line_number, line.to_string())),
+                Err(e) => {
+                    report.skipped_lines.push((line_number, line.to_string()));
+                    report.diagnostics.push(e.at_line(line_number));
+                },
+            }
+        }
+
+        report.diagnostics.extend(self.warnings[warnings..].iter().cloned());
+        report.diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+        report
+    }
+
+    /// Like `parse_into`, but keeps going after a bad line and returns every line error found
+    pub fn parse_into_collecting<T: ParseTarget>(&mut self, content: &str, target: &mut T) -> Vec<ParseDiagnostic> {
+        let mut errors = vec![];
+        for (line_idx, line) in log_lines(content).enumerate() {
+            let line_number = line_idx + 1;
+            if let Err(e) = self.parse_line(line_number, line, target) {
+                errors.push(e.at_line(line_number));
+            }
+        }
+
+        errors
+    }
+
+    /// What the line became, `None` for blank, comment and (when lenient) unrecognized lines
+    fn parse_line<T: ParseTarget>(&mut self, line_number: usize, line: &str, target: &mut T) -> Result<Option<ParsedLine>, ParseDiagnostic> {
+        // `lines()` splits on CRLF, but a CRLF file missing its final newline still leaves a '\r'
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if is_blank_or_comment(line) {
+            return Ok(None);
+        }
+
+        let previous_entry = self.context.last_watch_entry.clone();
+        let mut claimed_error = None;
+        let mut parsed = None;
+        for parser in self.parsers.iter_mut() {
+            match parser.parse_line(line, &mut self.context, &self.config) {
+                Ok(parsed_line) => {
+                    parsed = Some(parsed_line);
+                    break;
+                },
+                Err(e) if claimed_error.is_none() && parser.claims(line) => claimed_error = Some(e),
+                Err(_) => {},
+            }
+        }
+
+        if let Some(ParsedLine::Watch(entry)) = &mut parsed {
+            if self.config.keep_source_lines {
+                entry.source_line = Some(line.to_string());
+            }
+        }
+
+        match &parsed {
+            Some(ParsedLine::Date(date)) => self.context.notify_new_current_date(*date)?,
+            Some(ParsedLine::Tag(tag)) => self.context.notify_new_session_tag(Some(tag.clone()))?,
+            Some(ParsedLine::Watch(entry)) => {
+                if let Some(previous_entry) = previous_entry {
+                    self.check_entry_order(line_number, &previous_entry, entry);
+                }
+                self.check_session_length(line_number, entry);
+                target.push_watch_entry(entry.clone())?;
+            },
+            Some(ParsedLine::Title(title)) => {
+                let anime_id = target.anime_id_for_title(title)?;
+                self.context.notify_new_current_anime(anime_id)?;
+            },
+            None => {
+                if let Some(e) = claimed_error {
+                    return Err(e);
+                }
+
+                let message = format!("Unrecognized line: \"{}\"", line);
+                if self.config.strict_unknown {
+                    return Err(ParseDiagnostic::error(message));
+                }
+                self.warnings.push(ParseDiagnostic::warning(message).at_line(line_number));
+            },
+        }
+
+        Ok(parsed)
+    }
+
+    /// Warns about an entry that overlaps or goes back in episodes from the previous one of the same anime.
+    /// A repeat of the previous entry is left to `SimpleDatabase::find_duplicate_entries` instead.
+    fn check_entry_order(&mut self, line_number: usize, previous_entry: &WatchEntry, entry: &WatchEntry) {
+        if let Some(previous_end_time) = previous_entry.end_time {
+            if entry.start_time < previous_end_time && !entry.same_session(previous_entry) {
+                self.warnings.push(ParseDiagnostic::warning(format!("Entry starting at {} overlaps previous entry ending at {}", entry.start_time, previous_end_time)).at_line(line_number));
+            }
+        }
+
+        if let (Some(previous_episode), Some(episode)) = (previous_entry.episode.end(), entry.episode.start()) {
+            if (entry.episode.season(), episode) < (previous_entry.episode.season(), previous_episode) {
+                self.warnings.push(ParseDiagnostic::warning(format!("Episode {} comes after episode {}", episode, previous_episode)).at_line(line_number));
+            }
+        }
+    }
+
+    /// Warns about a session too short to be a whole episode, e.g. `16:40 - 16:41 05`
+    fn check_session_length(&mut self, line_number: usize, entry: &WatchEntry) {
+        let Some(duration) = entry.duration() else {
+            return;
+        };
+        // Zero-length markers are intended as such when allowed
+        let marker = self.config.allow_zero_duration && duration == Duration::zero();
+        if duration < Duration::minutes(self.config.min_session_minutes) && !entry.episode.is_short_form() && !marker {
+            self.warnings.push(ParseDiagnostic::warning(format!("Session for episode {} lasts {} min, less than {}", entry.episode, duration.num_minutes(), self.config.min_session_minutes)).at_line(line_number));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::prelude::*;
 
     use super::*;
 
+    /// A fresh context on `date` (`DD/MM/YYYY`), under `anime_id`
+    fn context_on(date: &str, anime_id: AnimeID) -> ParsingContext {
+        let mut context = ParsingContext::new();
+        context.notify_new_current_date(NaiveDate::parse_from_str(date, "%d/%m/%Y").unwrap()).unwrap();
+        context.notify_new_current_anime(anime_id).unwrap();
+        context
+    }
+
     #[test]
     fn date_line_ok() {
         let line = "10/02/2022";
@@ -220,6 +877,24 @@ mod tests {
         assert_eq!(date, expected);
     }
 
+    #[test]
+    fn date_line_weekday() {
+        let expected = NaiveDate::parse_from_str("19/03/2022", "%d/%m/%Y").unwrap();
+        assert_eq!(DateLineParser.parse("Sat 19/03/2022").unwrap(), expected);
+        assert_eq!(DateLineParser.parse("sat 19/03/2022 // weekend").unwrap(), expected);
+        assert_eq!(DateLineParser.parse("19/03/2022").unwrap(), expected);
+
+        let wrong_weekday = DateLineParser.parse("Fri 19/03/2022").unwrap_err();
+        assert_eq!(wrong_weekday.message, "19/03/2022 is a Sat, not a Fri");
+        assert!(DateLineParser.parse("Xyz 19/03/2022").is_err());
+        assert!(DateLineParser.parse("Saturday 19/03/2022").is_err());
+
+        // The mismatch is reported as such, not as an unrecognized line
+        let mut parser = FileParser::new(ParserConfig::default());
+        let err = parser.parse("Fri 19/03/2022\n").unwrap_err();
+        assert_eq!(err.to_string(), "Line 1: 19/03/2022 is a Sat, not a Fri");
+    }
+
     #[test]
     fn date_line_fail() {
         let line = "Weird stuff";
@@ -241,32 +916,239 @@ mod tests {
         let line2 = "10:00 - 12:00 12 {Gary}";
         let line3 = "10:00 - 12:00 12";
 
-        let mut context = ParsingContext{
-            current_date: Some(NaiveDate::parse_from_str("10/02/2022", "%d/%m/%Y").unwrap()),
-            current_anime: Some(1),
-            last_company: None,
-            last_watch_entry: None,
-        };
+        let mut context = context_on("10/02/2022", 1);
 
-        let watch_line = WatchLineParser{context: &mut context}.parse(line1).unwrap();
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line1).unwrap();
         assert_eq!(watch_line.start_time.time(), NaiveTime::parse_from_str("10:00", "%H:%M").unwrap());
-        assert_eq!(watch_line.end_time.time(), NaiveTime::parse_from_str("12:00", "%H:%M").unwrap());
+        assert_eq!(watch_line.end_time.unwrap().time(), NaiveTime::parse_from_str("12:00", "%H:%M").unwrap());
         assert_eq!(watch_line.episode, Episode::from("12").unwrap());
         assert_eq!(watch_line.company, Some(Company::from_str("{Gary, Amim}").unwrap()));
 
-        let watch_line = WatchLineParser{context: &mut context}.parse(line2).unwrap();
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line2).unwrap();
         assert_eq!(watch_line.start_time.time(), NaiveTime::parse_from_str("10:00", "%H:%M").unwrap());
-        assert_eq!(watch_line.end_time.time(), NaiveTime::parse_from_str("12:00", "%H:%M").unwrap());
+        assert_eq!(watch_line.end_time.unwrap().time(), NaiveTime::parse_from_str("12:00", "%H:%M").unwrap());
         assert_eq!(watch_line.episode, Episode::from("12").unwrap());
         assert_eq!(watch_line.company, Some(Company::from_str("{Gary}").unwrap()));
 
-        let watch_line = WatchLineParser{context: &mut context}.parse(line3).unwrap();
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line3).unwrap();
         assert_eq!(watch_line.start_time.time(), NaiveTime::parse_from_str("10:00", "%H:%M").unwrap());
-        assert_eq!(watch_line.end_time.time(), NaiveTime::parse_from_str("12:00", "%H:%M").unwrap());
+        assert_eq!(watch_line.end_time.unwrap().time(), NaiveTime::parse_from_str("12:00", "%H:%M").unwrap());
         assert_eq!(watch_line.episode, Episode::from("12").unwrap());
         assert_eq!(watch_line.company, None);
     }
 
+    #[test]
+    fn watch_line_company_tag() {
+        let mut context = context_on("10/02/2022", 1);
+
+        let braces = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("10:00 - 12:00 12 {Gary, Amim}").unwrap();
+        let tag = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("12:00 - 13:00 13 [with: Gary, Amim]").unwrap();
+        assert_eq!(tag.company, braces.company);
+        assert_eq!(tag.episode, Episode::from("13").unwrap());
+
+        let tag = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("13:00 - 14:00 [14 -> 15] [with: Gary, Amim] // two at once").unwrap();
+        assert_eq!(tag.company, braces.company);
+        assert_eq!(tag.episode, Episode::from("[14 -> 15]").unwrap());
+
+        let no_episode = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("15:00 - 16:00 [with: Gary]");
+        assert!(no_episode.is_err());
+    }
+
+    #[test]
+    fn watch_line_next_episode_shorthand() {
+        let mut context = context_on("10/02/2022", 1);
+
+        let no_previous = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("15:36 - 16:00 +");
+        assert!(no_previous.is_err());
+
+        WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("15:36 - 16:00 03").unwrap();
+
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("16:00 - 16:24 + {Gary}").unwrap();
+        assert_eq!(watch_line.episode, Episode::Number(4));
+        assert_eq!(watch_line.company, Some(Company::from_str("{Gary}").unwrap()));
+
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("16:24 - 16:48 ++").unwrap();
+        assert_eq!(watch_line.episode, Episode::Number(6));
+
+        WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("17:00 - 17:24 S2E05").unwrap();
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("17:24 - 17:48 +").unwrap();
+        assert_eq!(watch_line.episode, Episode::Seasonal { season: 2, number: 6 });
+
+        context.notify_new_current_anime(2).unwrap();
+        let other_anime = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("17:00 - 17:24 +");
+        assert!(other_anime.is_err());
+
+        // Nothing comes after the last representable episode
+        let mut context = context_on("10/02/2022", 1);
+        WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("18:00 - 18:24 2147483647").unwrap();
+        let overflow = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("18:24 - 18:48 +");
+        assert_eq!(overflow.unwrap_err().message, "Episode \"+\" has no episode number to follow 2147483647");
+        WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("19:00 - 19:24 2147483640 -> 2147483647").unwrap();
+        assert!(WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("19:24 - 19:48 ++").is_err());
+    }
+
+    #[test]
+    fn watch_entry_for_another_anime_is_an_error() {
+        let start = NaiveDateTime::parse_from_str("10/02/2022 10:00", "%d/%m/%Y %H:%M").unwrap();
+        let end = NaiveDateTime::parse_from_str("10/02/2022 10:30", "%d/%m/%Y %H:%M").unwrap();
+        let mut context = ParsingContext::new();
+
+        context.notify_new_watch_entry(WatchEntry::new(1, start, end, Episode::Number(1), None)).unwrap();
+        assert_eq!(
+            context.notify_new_watch_entry(WatchEntry::new(2, start, end, Episode::Number(2), None)),
+            Err("Watch entry for anime 2 follows one for anime 1 without a new title".to_string())
+        );
+        assert_eq!(context.last_watch_entry.as_ref().map(|entry| entry.anime_id), Some(1));
+    }
+
+    #[test]
+    fn watch_line_company_separator() {
+        let mut context = context_on("10/02/2022", 1);
+        let semicolon = ParserConfig { company_separator: ';', ..ParserConfig::default() };
+
+        let commas = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("10:00 - 11:00 01 {Gary, Amim}").unwrap();
+        let semicolons = WatchLineParser{context: &mut context, config: &semicolon}.parse("11:00 - 12:00 02 {Gary; Amim}").unwrap();
+        assert_eq!(semicolons.company, commas.company);
+
+        let tag = WatchLineParser{context: &mut context, config: &semicolon}.parse("12:00 - 13:00 03 [with: Gary; Amim]").unwrap();
+        assert_eq!(tag.company, commas.company);
+    }
+
+    #[test]
+    fn watch_line_padded_range() {
+        let mut context = context_on("10/02/2022", 1);
+
+        for (line, start) in [("10:00 - 11:00 01->05", "10:00"), ("11:00 - 12:00 01 -> 5", "11:00"), ("12:00 - 13:00 1 ->05", "12:00")] {
+            let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line).unwrap();
+            assert_eq!(watch_line.start_time.time(), NaiveTime::parse_from_str(start, "%H:%M").unwrap());
+            assert_eq!(watch_line.episode, Episode::Range { start: 1, end: 5 });
+        }
+    }
+
+    #[test]
+    fn watch_line_with_comment() {
+        let mut context = context_on("10/02/2022", 1);
+
+        let line = "16:40 - 18:24 01 // skipped intro";
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line).unwrap();
+        assert_eq!(watch_line.start_time.time(), NaiveTime::parse_from_str("16:40", "%H:%M").unwrap());
+        assert_eq!(watch_line.end_time.unwrap().time(), NaiveTime::parse_from_str("18:24", "%H:%M").unwrap());
+        assert_eq!(watch_line.episode, Episode::from("01").unwrap());
+        assert_eq!(watch_line.company, None);
+
+        let line = "18:30 - 19:00 02 {Gary, Amim} // with friends";
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line).unwrap();
+        assert_eq!(watch_line.episode, Episode::from("02").unwrap());
+        assert_eq!(watch_line.company, Some(Company::from_str("{Gary, Amim}").unwrap()));
+    }
+
+    #[test]
+    fn watch_line_comment_only_outside_braces() {
+        let mut context = context_on("10/02/2022", 1);
+
+        let line = "16:40 - 18:24 01 {Gary // Amim}";
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line).unwrap();
+        assert_eq!(watch_line.company, Some(Company::from_str("{Gary // Amim}").unwrap()));
+    }
+
+    #[test]
+    fn watch_line_company_group_stops_at_first_brace() {
+        let mut context = context_on("10/02/2022", 1);
+
+        let line = "10:00 - 12:00 12 {Gary} // later joined by {Amim}";
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line).unwrap();
+        assert_eq!(watch_line.company, Some(Company::from_str("{Gary}").unwrap()));
+
+        let line = "12:00 - 13:00 13 {Gary} stray}";
+        assert!(WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line).is_err());
+    }
+
+    #[test]
+    fn watch_line_entry_tags() {
+        let mut context = context_on("10/02/2022", 1);
+
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("10:00 - 10:20 01 [dub] [bluray]").unwrap();
+        assert_eq!(watch_line.tags, vec!["dub", "bluray"]);
+        assert_eq!(watch_line.company, None);
+
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("10:20 - 10:40 02 {Gary} [src(bluray)][sub] // rewatch").unwrap();
+        assert_eq!(watch_line.tags, vec!["src(bluray)", "sub"]);
+        assert_eq!(watch_line.company, Some(Company::from_str("{Gary}").unwrap()));
+
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("10:40 - 11:00 [03,04] [with: Gary] [dub]").unwrap();
+        assert_eq!(watch_line.episode, Episode::List(vec![3, 4]));
+        assert_eq!(watch_line.company, Some(Company::from_str("{Gary}").unwrap()));
+        assert_eq!(watch_line.tags, vec!["dub"]);
+
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("11:00 - 11:20 05").unwrap();
+        assert!(watch_line.tags.is_empty());
+    }
+
+    #[test]
+    fn watch_line_twelve_hour_times() {
+        let mut context = context_on("19/03/2022", 1);
+        let config = ParserConfig { time_format: TimeFormat::TwelveHour, ..ParserConfig::default() };
+
+        let watch_line = WatchLineParser{context: &mut context, config: &config}.parse("04:40 PM - 06:24 PM 01").unwrap();
+        assert_eq!(watch_line.start_time.time(), NaiveTime::from_hms_opt(16, 40, 0).unwrap());
+        assert_eq!(watch_line.end_time.unwrap().time(), NaiveTime::from_hms_opt(18, 24, 0).unwrap());
+
+        let watch_line = WatchLineParser{context: &mut context, config: &config}.parse("11:30pm - 12:10am 02").unwrap();
+        assert_eq!(watch_line.start_time, NaiveDate::from_ymd_opt(2022, 3, 19).unwrap().and_hms_opt(23, 30, 0).unwrap());
+        assert_eq!(watch_line.end_time, Some(NaiveDate::from_ymd_opt(2022, 3, 20).unwrap().and_hms_opt(0, 10, 0).unwrap()));
+
+        assert!(WatchLineParser{context: &mut context, config: &config}.parse("01:00 - 01:20 03").unwrap_err().message.contains("missing AM or PM"));
+        assert!(WatchLineParser{context: &mut context, config: &config}.parse("13:00 PM 03").is_err());
+        assert!(WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("01:00 AM 03").unwrap_err().message.contains("12-hour time"));
+    }
+
+    #[test]
+    fn watch_line_episode_range_and_list() {
+        let mut context = context_on("10/02/2022", 1);
+
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("10:00 - 11:10 01 -> 03 {Gary}").unwrap();
+        assert_eq!(watch_line.episode, Episode::Range { start: 1, end: 3 });
+        assert_eq!(watch_line.company, Some(Company::from_str("{Gary}").unwrap()));
+
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("11:10 - 12:00 05 -> {Gary}").unwrap();
+        assert_eq!(watch_line.episode, Episode::RangeFrom(5));
+        assert_eq!(watch_line.company, Some(Company::from_str("{Gary}").unwrap()));
+
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("12:00 - 13:00 [05,07]").unwrap();
+        assert_eq!(watch_line.episode, Episode::List(vec![5, 7]));
+
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("14:00 - 14:40 ova {Gary}").unwrap();
+        assert_eq!(watch_line.episode, Episode::Special("OVA".to_string()));
+        assert_eq!(watch_line.company, Some(Company::from_str("{Gary}").unwrap()));
+    }
+
+    #[test]
+    fn watch_line_inline_date() {
+        let mut context = context_on("19/03/2022", 1);
+        let date = |date: &str| NaiveDate::parse_from_str(date, "%d/%m/%Y").unwrap();
+
+        let without_date = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("10:00 - 10:20 01").unwrap();
+        let same_day = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("19/03/2022 16:40 - 18:24 02").unwrap();
+        assert_eq!(without_date.start_time.date(), date("19/03/2022"));
+        assert_eq!(same_day.start_time, date("19/03/2022").and_hms_opt(16, 40, 0).unwrap());
+        assert_eq!(context.current_date, Some(date("19/03/2022")));
+
+        let earlier = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("18/03/2022 16:40 - 18:24 03");
+        assert_eq!(earlier.unwrap_err().message, "Inline date 18/03/2022 is before the current date 19/03/2022");
+
+        let new_day = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("  21/03/2022 09:00 - 09:30 03").unwrap();
+        assert_eq!(new_day.start_time, date("21/03/2022").and_hms_opt(9, 0, 0).unwrap());
+        assert_eq!((context.current_date, context.current_anime), (Some(date("21/03/2022")), Some(1)));
+
+        assert!(WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("31/02/2022 09:00 - 09:30 04").is_err());
+
+        // A rejected line doesn't move the context to its date
+        let before = context.clone();
+        assert!(WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("22/03/2022 10:00 - 10:00 04").is_err());
+        assert!(WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("22/03/2022 10:00 - 10:30 04 {Gary, +two}").is_err());
+        assert_eq!(context, before, "Rejected line shouldn't touch the context");
+    }
+
     #[test]
     fn anime_title_line_ok() {
         let line = "Erased:";
@@ -302,81 +1184,175 @@ mod tests {
         assert_eq!(title_line+":", line);
     }
 
+    #[test]
+    fn anime_title_line_utf8() {
+        assert_eq!(TitleLineParser.parse("進撃の巨人:").unwrap(), "進撃の巨人");
+        assert_eq!(TitleLineParser.parse("  進撃の巨人: The Final Season: // 最終").unwrap(), "進撃の巨人: The Final Season");
+        assert_eq!(TitleLineParser.parse("Café Terrace:").unwrap(), "Café Terrace");
+    }
+
+    #[test]
+    fn file_parser_multibyte_lines_do_not_panic() {
+        let content = "19/03/2022\n進撃の巨人:\n[再視聴]\n20:00 - 20:30 01 {ガリー, Amim}\nSáb 20/03/2022\n20:00 - 20:30 進撃\n20:00 ｜ 20:30 01\n１９/03/2022\né\n{進撃}\n";
+
+        let mut db = simple_database::SimpleDatabase::new();
+        let mut parser = FileParser::new(ParserConfig { strict_unknown: true, ..ParserConfig::default() });
+        let errors = parser.parse_into_collecting(content, &mut db);
+
+        assert_eq!(errors.iter().map(|error| error.line.unwrap()).collect::<Vec<_>>(), vec![5, 6, 7, 8, 9, 10]);
+        let anime = db.find_anime_by_name("進撃の巨人").unwrap();
+        let entry = anime.watch_entries().next().unwrap();
+        assert_eq!(entry.session_tag.as_deref(), Some("再視聴"));
+        assert!(entry.company.as_ref().unwrap().contains("ガリー"));
+    }
+
+    #[test]
+    fn anime_title_line_leading_character() {
+        assert_eq!(TitleLineParser.parse("\"Oshi no Ko\":").unwrap(), "\"Oshi no Ko\"");
+        assert_eq!(TitleLineParser.parse("鬼滅の刃:").unwrap(), "鬼滅の刃");
+        assert_eq!(TitleLineParser.parse("86:").unwrap(), "86");
+
+        assert!(TitleLineParser.parse("20:00:").is_err());
+        assert!(TitleLineParser.parse("  20:00 - 20:30 01:").is_err());
+        assert!(TitleLineParser.parse("19/03/2022:").is_err());
+        assert!(TitleLineParser.parse("[rewatch]:").is_err());
+        assert!(TitleLineParser.parse(" :").is_err());
+    }
+
     #[test]
     fn midnight_last() {
-        let mut context = ParsingContext {
-            current_anime: Some(1),
-            current_date: Some(NaiveDate::parse_from_str("10/02/2022", "%d/%m/%Y").unwrap()),
-            last_company: None,
-            last_watch_entry: None,
-        };
+        let mut context = context_on("10/02/2022", 1);
 
         let line1 = "23:00 - 23:40 12";
-        let watch_line1 = WatchLineParser{context: &mut context}.parse(line1).unwrap();
+        let watch_line1 = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line1).unwrap();
         let line2 = "00:00 - 00:10 13";
-        let watch_line2 = WatchLineParser{context: &mut context}.parse(line2).unwrap();
+        let watch_line2 = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line2).unwrap();
 
-        assert_eq!(watch_line1.start_time.date(), watch_line1.end_time.date(), "Dates should be the same");
-        assert_eq!(watch_line2.start_time.date(), watch_line2.end_time.date(), "Dates should be the same");
+        assert_eq!(watch_line1.start_time.date(), watch_line1.end_time.unwrap().date(), "Dates should be the same");
+        assert_eq!(watch_line2.start_time.date(), watch_line2.end_time.unwrap().date(), "Dates should be the same");
         assert!(watch_line1.start_time.date() != watch_line2.start_time.date(), "Dates should be different");
     }
 
     #[test]
     fn midnight_traverse() {
         let initial_date = NaiveDate::parse_from_str("10/02/2022", "%d/%m/%Y").unwrap();
-        let mut context = ParsingContext {
-            current_anime: Some(1),
-            current_date: Some(NaiveDate::parse_from_str("10/02/2022", "%d/%m/%Y").unwrap()),
-            last_company: None,
-            last_watch_entry: None,
-        };
+        let mut context = context_on("10/02/2022", 1);
 
         let line1 = "23:40 - 00:20 12";
-        let watch_line1 = WatchLineParser{context: &mut context}.parse(line1).unwrap();
+        let watch_line1 = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line1).unwrap();
 
         assert_eq!(watch_line1.start_time.date(), initial_date, "Dates should be the same");
-        assert_eq!(watch_line1.end_time.date(), initial_date.succ(), "Date should be the next day");
-        assert_eq!(context.current_date, Some(initial_date.succ()), "Date should be incremented");
+        assert_eq!(watch_line1.end_time.unwrap().date(), initial_date.succ_opt().unwrap(), "Date should be the next day");
+        assert_eq!(context.current_date, Some(initial_date.succ_opt().unwrap()), "Date should be incremented");
 
         let line2 = "00:20 - 00:30 13";
-        let watch_line2 = WatchLineParser{context: &mut context}.parse(line2).unwrap();
+        let watch_line2 = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line2).unwrap();
+
+        assert_eq!(watch_line2.start_time.date(), initial_date.succ_opt().unwrap(), "Dates should be the next day");
+        assert_eq!(watch_line2.end_time.unwrap().date(), initial_date.succ_opt().unwrap(), "Date should be the next day");
+        assert_eq!(context.current_date, Some(initial_date.succ_opt().unwrap()), "Date should be incremented");
 
-        assert_eq!(watch_line2.start_time.date(), initial_date.succ(), "Dates should be the next day");
-        assert_eq!(watch_line2.end_time.date(), initial_date.succ(), "Date should be the next day");
-        assert_eq!(context.current_date, Some(initial_date.succ()), "Date should be incremented");
+    }
 
+    #[test]
+    fn explicit_end_day_offset() {
+        let initial_date = NaiveDate::parse_from_str("10/02/2022", "%d/%m/%Y").unwrap();
+        let mut context = context_on("10/02/2022", 1);
+
+        let overnight = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("23:00 - 07:00+1 05").unwrap();
+        assert_eq!(overnight.start_time.date(), initial_date);
+        assert_eq!(overnight.end_time.unwrap().date(), initial_date.succ_opt().unwrap());
+        assert_eq!(overnight.duration(), Some(Duration::hours(8)));
+        assert_eq!(context.current_date, Some(initial_date.succ_opt().unwrap()));
+
+        // Longer than a day, which needs a higher maximum session
+        assert!(WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("10:00 - 12:00+2 06").is_err());
+        let long_sessions = ParserConfig { max_session_hours: 72, ..ParserConfig::default() };
+        let weekend = WatchLineParser{context: &mut context, config: &long_sessions}.parse("10:00 - 12:00+2 06").unwrap();
+        assert_eq!(weekend.start_time.date(), initial_date.succ_opt().unwrap());
+        assert_eq!(weekend.end_time.unwrap().date(), NaiveDate::from_ymd_opt(2022, 2, 13).unwrap());
+        assert_eq!(weekend.duration(), Some(Duration::hours(50)));
+        assert_eq!(context.current_date, NaiveDate::from_ymd_opt(2022, 2, 13));
+
+        // "+0" keeps the end on the start's day, so it can't come before the start
+        let same_day = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("13:00 - 13:30+0 07").unwrap();
+        assert_eq!(same_day.duration(), Some(Duration::minutes(30)));
+        assert!(WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("14:00 - 13:00+0 08").is_err());
+
+        let episode_first = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("09 23:00 - 01:00+1").unwrap();
+        assert_eq!(episode_first.duration(), Some(Duration::hours(2)));
+    }
+
+    #[test]
+    fn midnight_rollover_needs_a_long_step_back() {
+        let time = |time: &str| NaiveTime::parse_from_str(time, "%H:%M").unwrap();
+        let after = |last_time: &str| ParsingContext { last_entry_time: Some(time(last_time)), ..ParsingContext::new() };
+        assert!(after("23:30").starts_after_midnight(time("00:15")));
+        assert!(after("22:00").starts_after_midnight(time("09:59")));
+        assert!(!after("22:00").starts_after_midnight(time("10:00")));
+        assert!(!after("12:00").starts_after_midnight(time("10:00")));
+        assert!(!after("10:00").starts_after_midnight(time("10:00")));
+        assert!(!ParsingContext::new().starts_after_midnight(time("00:15")));
+
+        // An overlapping entry stays on the same day, a jump back past midnight moves on
+        let mut context = context_on("10/02/2022", 1);
+        let parse = |context: &mut ParsingContext, line: &str| WatchLineParser{context, config: &ParserConfig::default()}.parse(line).unwrap();
+        parse(&mut context, "20:00 - 22:00 01");
+        assert_eq!(parse(&mut context, "21:30 - 22:30 02").start_time.date(), NaiveDate::from_ymd_opt(2022, 2, 10).unwrap());
+        assert_eq!(parse(&mut context, "00:15 - 00:40 03").start_time.date(), NaiveDate::from_ymd_opt(2022, 2, 11).unwrap());
+    }
+
+    #[test]
+    fn rejected_day_changes_keep_the_context() {
+        let parse = |context: &mut ParsingContext, line: &str| WatchLineParser{context, config: &ParserConfig::default()}.parse(line);
+        let mut context = context_on("10/02/2022", 1);
+        parse(&mut context, "23:00 - 23:30 01").unwrap();
+        let before = context.clone();
+
+        // Starting after midnight
+        assert!(parse(&mut context, "01:00 - 01:00 02").is_err());
+        assert!(parse(&mut context, "01:00 - 01:30 02 {Gary, +two}").is_err());
+        assert_eq!(context, before, "Rejected rollover shouldn't touch the context");
+
+        // Explicit day offset
+        assert!(parse(&mut context, "23:40 - 00:10+1 02 {Gary, +two}").is_err());
+        assert!(parse(&mut context, "23:40 - 23:00+0 02").is_err());
+        assert_eq!(context, before, "Rejected day offset shouldn't touch the context");
+
+        // Ending after midnight
+        assert!(parse(&mut context, "23:40 - 00:10 02 {Gary, +two}").is_err());
+        assert_eq!(context, before, "Rejected midnight crossing shouldn't touch the context");
+
+        let entry = parse(&mut context, "23:40 - 00:10 02").unwrap();
+        assert_eq!(entry.start_time.date(), NaiveDate::from_ymd_opt(2022, 2, 10).unwrap());
+        assert_eq!(context.current_date, NaiveDate::from_ymd_opt(2022, 2, 11));
     }
 
     #[test]
     fn midnight_last_and_traverse() {
         let initial_date = NaiveDate::parse_from_str("10/02/2022", "%d/%m/%Y").unwrap();
-        let mut context = ParsingContext {
-            current_anime: Some(1),
-            current_date: Some(NaiveDate::parse_from_str("10/02/2022", "%d/%m/%Y").unwrap()),
-            last_company: None,
-            last_watch_entry: None,
-        };
+        let mut context = context_on("10/02/2022", 1);
 
         let line0 = "23:00 - 23:40 12";
-        let watch_line0 = WatchLineParser{context: &mut context}.parse(line0).unwrap();
+        let watch_line0 = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line0).unwrap();
 
         assert_eq!(watch_line0.start_time.date(), initial_date, "Dates should be the same");
-        assert_eq!(watch_line0.end_time.date(), initial_date, "Dates should be the same");
+        assert_eq!(watch_line0.end_time.unwrap().date(), initial_date, "Dates should be the same");
         assert_eq!(context.current_date, Some(initial_date), "Date should be the same");
 
         let line1 = "23:00 - 02:10 12"; // Traverse to next day
-        let watch_line1 = WatchLineParser{context: &mut context}.parse(line1).unwrap();
+        let watch_line1 = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line1).unwrap();
 
         assert_eq!(watch_line1.start_time.date(), initial_date, "Dates should be the same");
-        assert_eq!(watch_line1.end_time.date(), initial_date.succ(), "Date should be the next day");
-        assert_eq!(context.current_date, Some(initial_date.succ()), "Date should be incremented");
+        assert_eq!(watch_line1.end_time.unwrap().date(), initial_date.succ_opt().unwrap(), "Date should be the next day");
+        assert_eq!(context.current_date, Some(initial_date.succ_opt().unwrap()), "Date should be incremented");
 
         let line2 = "02:10 - 00:00 13"; // Traverse to next day
-        let watch_line2 = WatchLineParser{context: &mut context}.parse(line2).unwrap();
+        let watch_line2 = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line2).unwrap();
 
-        assert_eq!(watch_line2.start_time.date(), initial_date.succ(), "Dates should be the next day");
-        assert_eq!(watch_line2.end_time.date(), initial_date.succ().succ(), "Date should be the nexts next day");
-        assert_eq!(context.current_date, Some(initial_date.succ().succ()), "Date should be incremented twice");
+        assert_eq!(watch_line2.start_time.date(), initial_date.succ_opt().unwrap(), "Dates should be the next day");
+        assert_eq!(watch_line2.end_time.unwrap().date(), initial_date.succ_opt().unwrap().succ_opt().unwrap(), "Date should be the nexts next day");
+        assert_eq!(context.current_date, Some(initial_date.succ_opt().unwrap().succ_opt().unwrap()), "Date should be incremented twice");
 
     }
 
@@ -390,17 +1366,12 @@ mod tests {
         let anime_title_line = TitleLineParser.parse(line).unwrap();
         assert_eq!(anime_title_line, "Evangelion: 1.0 You Are (Not) Alone");        
 
-        let mut context = ParsingContext {
-            current_date: Some(NaiveDate::parse_from_str("10/02/2022", "%d/%m/%Y").unwrap()),
-            current_anime: Some(1),
-            last_company: None,
-            last_watch_entry: None,
-        };
+        let mut context = context_on("10/02/2022", 1);
 
         let line = "16:40 - 18:24 01 {Vinicius Russo}";
-        let watch_line = WatchLineParser{context: &mut context}.parse(line).unwrap();
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line).unwrap();
         assert_eq!(watch_line.start_time.time(), NaiveTime::parse_from_str("16:40", "%H:%M").unwrap());
-        assert_eq!(watch_line.end_time.time(), NaiveTime::parse_from_str("18:24", "%H:%M").unwrap());
+        assert_eq!(watch_line.end_time.unwrap().time(), NaiveTime::parse_from_str("18:24", "%H:%M").unwrap());
         assert_eq!(watch_line.episode, Episode::from("01").unwrap());
         assert_eq!(watch_line.company, Some(Company::from_str("{Vinicius Russo}").unwrap()));
 
@@ -409,23 +1380,23 @@ mod tests {
         assert_eq!(anime_title_line, "One Pace: Reverie");
 
         let line = "20:09 - 20:46 01 {Lucas Romero}";
-        let watch_line = WatchLineParser{context: &mut context}.parse(line).unwrap();
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line).unwrap();
         assert_eq!(watch_line.start_time.time(), NaiveTime::parse_from_str("20:09", "%H:%M").unwrap());
-        assert_eq!(watch_line.end_time.time(), NaiveTime::parse_from_str("20:46", "%H:%M").unwrap());
+        assert_eq!(watch_line.end_time.unwrap().time(), NaiveTime::parse_from_str("20:46", "%H:%M").unwrap());
         assert_eq!(watch_line.episode, Episode::from("01").unwrap());
         assert_eq!(watch_line.company, Some(Company::from_str("{Lucas Romero}").unwrap()));
 
         let line = "20:46 - 21:26 02 {Lucas Romero}";
-        let watch_line = WatchLineParser{context: &mut context}.parse(line).unwrap();
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line).unwrap();
         assert_eq!(watch_line.start_time.time(), NaiveTime::parse_from_str("20:46", "%H:%M").unwrap());
-        assert_eq!(watch_line.end_time.time(), NaiveTime::parse_from_str("21:26", "%H:%M").unwrap());
+        assert_eq!(watch_line.end_time.unwrap().time(), NaiveTime::parse_from_str("21:26", "%H:%M").unwrap());
         assert_eq!(watch_line.episode, Episode::from("02").unwrap());
         assert_eq!(watch_line.company, Some(Company::from_str("{Lucas Romero}").unwrap()));
 
         let line = "21:27 - 22:04 03 {Lucas Romero}";
-        let watch_line = WatchLineParser{context: &mut context}.parse(line).unwrap();
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line).unwrap();
         assert_eq!(watch_line.start_time.time(), NaiveTime::parse_from_str("21:27", "%H:%M").unwrap());
-        assert_eq!(watch_line.end_time.time(), NaiveTime::parse_from_str("22:04", "%H:%M").unwrap());
+        assert_eq!(watch_line.end_time.unwrap().time(), NaiveTime::parse_from_str("22:04", "%H:%M").unwrap());
         assert_eq!(watch_line.episode, Episode::from("03").unwrap());
         assert_eq!(watch_line.company, Some(Company::from_str("{Lucas Romero}").unwrap()));
 
@@ -434,17 +1405,572 @@ mod tests {
         assert_eq!(anime_title_line, "One Pace: Wano");
 
         let line = "22:11 - 22:35 01 {Lucas Romero}";
-        let watch_line = WatchLineParser{context: &mut context}.parse(line).unwrap();
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line).unwrap();
         assert_eq!(watch_line.start_time.time(), NaiveTime::parse_from_str("22:11", "%H:%M").unwrap());
-        assert_eq!(watch_line.end_time.time(), NaiveTime::parse_from_str("22:35", "%H:%M").unwrap());
+        assert_eq!(watch_line.end_time.unwrap().time(), NaiveTime::parse_from_str("22:35", "%H:%M").unwrap());
         assert_eq!(watch_line.episode, Episode::from("01").unwrap());
         assert_eq!(watch_line.company, Some(Company::from_str("{Lucas Romero}").unwrap()));
         
         let line = "22:44 - 23:17 02";
-        let watch_line = WatchLineParser{context: &mut context}.parse(line).unwrap();
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse(line).unwrap();
         assert_eq!(watch_line.start_time.time(), NaiveTime::parse_from_str("22:44", "%H:%M").unwrap());
-        assert_eq!(watch_line.end_time.time(), NaiveTime::parse_from_str("23:17", "%H:%M").unwrap());
+        assert_eq!(watch_line.end_time.unwrap().time(), NaiveTime::parse_from_str("23:17", "%H:%M").unwrap());
         assert_eq!(watch_line.episode, Episode::from("02").unwrap());
         assert_eq!(watch_line.company, None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn real_sample_titles_share_one_context() {
+        let content = "19/03/2022\n\
+            Evangelion: 1.0 You Are (Not) Alone: // 1.11\n\
+            16:40 - 18:24 01 {Vinicius Russo}\n\
+            One Pace: Reverie:\n\
+            20:09 - 20:46 01 {Lucas Romero}\n\
+            20:46 - 21:26 02 {Lucas Romero}\n\
+            21:27 - 22:04 03 {Lucas Romero}\n\
+            One Pace: Wano:\n\
+            22:11 - 22:35 01 {Lucas Romero}\n\
+            22:44 - 23:17 02\n\
+            One Pace: Reverie:\n\
+            [rewatch]\n\
+            23:50 - 00:20 04\n\
+            One Pace: Wano:\n\
+            00:30 - 00:55 03\n";
+
+        let mut parser = FileParser::new(ParserConfig { strict_unknown: true, ..ParserConfig::default() });
+        let database = parser.parse(content).unwrap();
+        assert!(parser.warnings().is_empty(), "{:?}", parser.warnings());
+
+        let on = |day: u32, time: &str| NaiveDate::from_ymd_opt(2022, 3, day).unwrap().and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap());
+        let entries: Vec<(&str, String, NaiveDateTime, Option<&str>)> = database.watch_entries.iter()
+            .map(|entry| (database.animes[entry.anime_id].as_str(), entry.episode.to_string(), entry.start_time, entry.session_tag.as_deref()))
+            .collect();
+        assert_eq!(entries, vec![
+            ("Evangelion: 1.0 You Are (Not) Alone", "01".to_string(), on(19, "16:40"), None),
+            ("One Pace: Reverie", "01".to_string(), on(19, "20:09"), None),
+            ("One Pace: Reverie", "02".to_string(), on(19, "20:46"), None),
+            ("One Pace: Reverie", "03".to_string(), on(19, "21:27"), None),
+            ("One Pace: Wano", "01".to_string(), on(19, "22:11"), None),
+            ("One Pace: Wano", "02".to_string(), on(19, "22:44"), None),
+            ("One Pace: Reverie", "04".to_string(), on(19, "23:50"), Some("rewatch")),
+            ("One Pace: Wano", "03".to_string(), on(20, "00:30"), None),
+        ]);
+        assert_eq!(database.watch_entries[6].end_time, Some(on(20, "00:20")));
+
+        // The context ends on the last title, on the day after the midnight crossing
+        assert_eq!(parser.context().current_date(), Some(NaiveDate::from_ymd_opt(2022, 3, 20).unwrap()));
+        assert_eq!(parser.context().current_anime, Some(2));
+        assert_eq!(parser.context().current_session_tag, None);
+
+        // A title starting after midnight still moves on to the next day
+        let database = FileParser::new(ParserConfig::default()).parse("19/03/2022\nErased:\n23:00 - 23:40 01\nOne Pace: Wano:\n00:10 - 00:40 01\n").unwrap();
+        assert_eq!(database.watch_entries[1].start_time, on(20, "00:10"));
+    }
+
+    #[test]
+    fn file_parser_lenient_skips_unknown_line() {
+        let content = "19/03/2022\nOne Pace: Wano:\n22:11 - 22:35 01\nsome stray text\n22:44 - 23:17 02\n";
+
+        let mut parser = FileParser::new(ParserConfig::default());
+        let database = parser.parse(content).unwrap();
+
+        assert_eq!(database.animes, vec!["One Pace: Wano".to_string()]);
+        assert_eq!(database.watch_entries.len(), 2);
+        assert_eq!(parser.warnings().len(), 1);
+        assert!(parser.warnings()[0].message.contains("some stray text"));
+    }
+
+    #[test]
+    fn file_parser_strict_rejects_unknown_line() {
+        let content = "19/03/2022\nOne Pace: Wano:\n22:11 - 22:35 01\nsome stray text\n22:44 - 23:17 02\n";
+
+        let mut parser = FileParser::new(ParserConfig { strict_unknown: true, ..ParserConfig::default() });
+        let err = parser.parse(content).unwrap_err();
+
+        assert_eq!(err.line, Some(4), "Unexpected diagnostic: {}", err);
+        assert!(err.message.contains("Unrecognized line"), "Unexpected diagnostic: {}", err);
+    }
+
+    #[test]
+    fn file_parser_keeps_configured_timezone() {
+        let content = "19/03/2022\nOne Pace: Wano:\n22:11 - 22:35 01\n";
+
+        let mut parser = FileParser::new(ParserConfig::default());
+        let database = parser.parse(content).unwrap();
+        assert_eq!(database.timezone, None);
+
+        let config = ParserConfig { timezone: Some(chrono_tz::America::Sao_Paulo), ..ParserConfig::default() };
+        let mut parser = FileParser::new(config.clone());
+        let database = parser.parse(content).unwrap();
+        assert_eq!(database.timezone, Some(chrono_tz::America::Sao_Paulo));
+
+        let entry = &database.watch_entries[0];
+        assert_eq!(entry.start_time_in(&database.timezone.unwrap()).unwrap().to_rfc3339(), "2022-03-19T22:11:00-03:00");
+
+        let mut db = crate::database::simple_database::SimpleDatabase::new();
+        FileParser::new(ParserConfig::default()).parse_into(content, &mut db).unwrap();
+        assert_eq!(db.timezone(), None);
+        FileParser::new(config).parse_into("20/03/2022\nErased:\n10:00 - 10:30 01\n", &mut db).unwrap();
+        assert_eq!(db.timezone(), Some(chrono_tz::America::Sao_Paulo));
+    }
+
+    #[test]
+    fn file_parser_resumes_from_saved_context() {
+        let head = "19/03/2022\nOne Pace: Wano:\n22:11 - 22:35 01\n";
+        let tail = "22:44 - 23:17 02\n20/03/2022\nOne Pace: Wano:\n10:00 - 10:30 03\n";
+
+        let mut parser = FileParser::new(ParserConfig::default());
+        parser.parse(head).unwrap();
+        let saved = parser.context().clone();
+
+        let mut parser = FileParser::with_context(ParserConfig::default(), saved);
+        let database = parser.parse(tail).unwrap();
+
+        assert_eq!(database.watch_entries.len(), 2);
+        assert_eq!(database.watch_entries[0].episode, Episode::from("02").unwrap());
+        assert_eq!(database.watch_entries[0].start_time.date(), NaiveDate::parse_from_str("19/03/2022", "%d/%m/%Y").unwrap());
+        assert_eq!(database.watch_entries[1].start_time.date(), NaiveDate::parse_from_str("20/03/2022", "%d/%m/%Y").unwrap());
+    }
+
+    #[test]
+    fn file_parser_resumed_context_still_checks_dates() {
+        let mut parser = FileParser::new(ParserConfig::default());
+        parser.parse("19/03/2022\nOne Pace: Wano:\n22:11 - 22:35 01\n").unwrap();
+
+        let mut parser = FileParser::with_context(ParserConfig::default(), parser.context().clone());
+        assert!(parser.parse("18/03/2022\n").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parsing_context_serde_round_trip() {
+        let mut parser = FileParser::new(ParserConfig::default());
+        parser.parse("19/03/2022\nOne Pace: Wano:\n22:11 - 22:35 01 {Lucas Romero}\n").unwrap();
+        let context = parser.context().clone();
+        assert!(context.last_watch_entry.is_some());
+
+        let serialized = serde_json::to_string(&context).unwrap();
+        let restored: ParsingContext = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(restored, context);
+    }
+
+    #[test]
+    fn file_parser_indented_watch_lines() {
+        let parse = |content: &str| {
+            let mut db = simple_database::SimpleDatabase::new();
+            FileParser::new(ParserConfig { strict_unknown: true, ..ParserConfig::default() }).parse_into(content, &mut db).unwrap();
+            let mut entries: Vec<WatchEntry> = db.animes().flat_map(|anime| anime.watch_entries().cloned()).collect();
+            entries.sort_by_key(|entry| entry.start_time);
+            entries
+        };
+
+        let flat = parse("19/03/2022\nErased:\n20:00 - 20:30 01 {Gary}\n20:30 - 21:00 02\n");
+        let tabs = parse("19/03/2022\nErased:\n\t20:00 - 20:30 01 {Gary}\n\t20:30 - 21:00 02\n");
+        let spaces = parse("19/03/2022\nErased:\n    20:00 - 20:30 01 {Gary}\n  20:30 - 21:00 02\n");
+
+        assert_eq!(flat.len(), 2);
+        assert_eq!(tabs, flat);
+        assert_eq!(spaces, flat);
+    }
+
+    #[test]
+    fn file_parser_creates_animes_in_database() {
+        let content = "19/03/2022\n\
+            One Pace: Reverie:\n\
+            20:09 - 20:46 01 {Lucas Romero}\n\
+            20:46 - 21:26 02 {Lucas Romero}\n\
+            One Pace: Wano:\n\
+            22:11 - 22:35 01 {Lucas Romero}\n\
+            20/03/2022\n\
+            One Pace: Reverie:\n\
+            10:00 - 10:40 03\n";
+
+        let mut db = simple_database::SimpleDatabase::new();
+        let mut parser = FileParser::new(ParserConfig::default());
+        parser.parse_into(content, &mut db).unwrap();
+
+        let reverie = db.find_anime_by_name("One Pace: Reverie").unwrap();
+        let reverie_id = reverie.id();
+        let episodes: Vec<Episode> = reverie.watch_entries().map(|entry| entry.episode.clone()).collect();
+        assert_eq!(episodes, vec![Episode::from("01").unwrap(), Episode::from("02").unwrap(), Episode::from("03").unwrap()]);
+        assert!(reverie.watch_entries().all(|entry| entry.anime_id == reverie_id));
+
+        let wano = db.find_anime_by_name("One Pace: Wano").unwrap();
+        let wano_id = wano.id();
+        assert_eq!(wano.watch_entries().count(), 1);
+        assert!(wano.watch_entries().all(|entry| entry.anime_id == wano_id));
+        assert_ne!(reverie_id, wano_id);
+    }
+
+    #[test]
+    fn tag_line_ok() {
+        assert_eq!(TagLineParser.parse("[marathon]").unwrap(), "marathon");
+        assert_eq!(TagLineParser.parse("  [src(bluray)] // comment").unwrap(), "src(bluray)");
+        assert!(TagLineParser.parse("[marathon").is_err());
+        assert!(TagLineParser.parse("[[marathon]]").is_err());
+        assert!(TagLineParser.parse("Erased:").is_err());
+    }
+
+    #[test]
+    fn session_tag_applies_until_next_title() {
+        let content = "19/03/2022\n\
+            One Pace: Reverie:\n\
+            [marathon]\n\
+            20:09 - 20:46 01\n\
+            20:46 - 21:26 02\n\
+            21:27 - 22:04 03\n\
+            One Pace: Wano:\n\
+            22:11 - 22:35 01\n";
+
+        let mut parser = FileParser::new(ParserConfig::default());
+        let database = parser.parse(content).unwrap();
+
+        let tags: Vec<Option<&str>> = database.watch_entries.iter().map(|entry| entry.session_tag.as_deref()).collect();
+        assert_eq!(tags, vec![Some("marathon"), Some("marathon"), Some("marathon"), None]);
+    }
+
+    #[test]
+    fn file_parser_warns_on_overlap_and_episode_order() {
+        let content = "19/03/2022\n\
+            One Pace: Wano:\n\
+            20:00 - 20:30 02\n\
+            20:20 - 20:50 03\n\
+            21:00 - 21:30 01\n";
+
+        let mut parser = FileParser::new(ParserConfig::default());
+        let database = parser.parse(content).unwrap();
+
+        assert_eq!(database.watch_entries.len(), 3);
+        assert_eq!(parser.warnings().len(), 2);
+        assert!(parser.warnings()[0].line == Some(4) && parser.warnings()[0].message.contains("overlaps"));
+        assert!(parser.warnings()[1].line == Some(5) && parser.warnings()[1].message.contains("Episode 1 comes after episode 3"));
+
+        // A pasted duplicate isn't an overlap
+        let mut parser = FileParser::new(ParserConfig::default());
+        parser.parse("19/03/2022\nOne Pace: Wano:\n20:00 - 20:30 02\n20:00 - 20:30 02\n").unwrap();
+        assert!(parser.warnings().is_empty());
+    }
+
+    #[test]
+    fn file_parser_warns_on_short_sessions() {
+        let content = "19/03/2022\n\
+            One Pace: Wano:\n\
+            16:40 - 16:41 05\n\
+            16:41 - 16:43 OP\n\
+            16:43 - 16:46 06\n";
+
+        let mut parser = FileParser::new(ParserConfig::default());
+        parser.parse(content).unwrap();
+
+        assert_eq!(parser.warnings().len(), 1);
+        assert_eq!(parser.warnings()[0].to_string(), "Line 3: Session for episode 05 lasts 1 min, less than 3");
+
+        let mut parser = FileParser::new(ParserConfig { min_session_minutes: 1, ..ParserConfig::default() });
+        parser.parse(content).unwrap();
+        assert!(parser.warnings().is_empty());
+
+        let mut parser = FileParser::new(ParserConfig { allow_zero_duration: true, ..ParserConfig::default() });
+        parser.parse("19/03/2022\nOne Pace: Wano:\n16:40 - 16:40 05\n16:40 - 16:41 06\n").unwrap();
+        assert_eq!(parser.warnings().len(), 1, "Only the 1 min session is suspicious, not the marker");
+        assert_eq!(parser.warnings()[0].line, Some(4));
+    }
+
+    #[test]
+    fn file_parser_collects_every_error() {
+        let content = "19/03/2022\nOne Pace: Wano:\nstray\n22:11 - 22:35 01\n18/03/2022\nmore stray\n";
+
+        let mut parser = FileParser::new(ParserConfig { strict_unknown: true, ..ParserConfig::default() });
+        let errors = parser.parse_into_collecting(content, &mut Database::default());
+
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0].line, Some(3));
+        assert_eq!(errors[1].line, Some(5));
+        assert_eq!(errors[2].line, Some(6));
+    }
+
+    #[test]
+    fn file_parser_watch_line_without_title() {
+        let content = "19/03/2022\n\n20:00 - 20:30 01\n";
+
+        let mut parser = FileParser::new(ParserConfig::default());
+        let error = parser.parse_into(content, &mut Database::default()).unwrap_err();
+
+        assert_eq!(error.severity, Severity::Error);
+        assert_eq!(error.line, Some(3));
+        assert_eq!(error.to_string(), "Line 3: No current anime: did you forget a title line ending with ':'?");
+    }
+
+    #[test]
+    fn file_parser_next_season_is_in_order() {
+        let content = "19/03/2022\nErased:\n20:00 - 20:30 S1E13\n20:30 - 21:00 S2E01\n21:00 - 21:30 S1E02\n";
+
+        let mut parser = FileParser::new(ParserConfig::default());
+        parser.parse_into(content, &mut Database::default()).unwrap();
+
+        assert_eq!(parser.warnings().len(), 1);
+        assert_eq!(parser.warnings()[0].line, Some(5));
+    }
+
+    #[test]
+    fn file_parser_skip_errors_keeps_both_severities() {
+        let content = "19/03/2022\nErased:\n20:00 - 20:30 01\n20:20 - 20:50 02\n99/99/2022\n21:00 - 21:30 03\n";
+
+        let mut database = Database::default();
+        let mut parser = FileParser::new(ParserConfig { strict_unknown: true, skip_errors: true, ..ParserConfig::default() });
+        parser.parse_into(content, &mut database).unwrap();
+
+        assert_eq!(database.watch_entries.len(), 3);
+        assert_eq!(parser.warnings().len(), 1);
+        assert_eq!((parser.warnings()[0].severity, parser.warnings()[0].line), (Severity::Warning, Some(4)));
+        assert_eq!(parser.errors().len(), 1);
+        assert_eq!((parser.errors()[0].severity, parser.errors()[0].line), (Severity::Error, Some(5)));
+
+        let mut parser = FileParser::new(ParserConfig { strict_unknown: true, ..ParserConfig::default() });
+        let err = parser.parse_into(content, &mut Database::default()).unwrap_err();
+        assert_eq!(err.to_string(), "Line 5: Date parse error: input is out of range");
+    }
+
+    #[test]
+    fn file_parser_skipped_inline_date_keeps_the_day() {
+        let content = "19/03/2022\nA:\n20/03/2022 10:00 - 10:00 01\n11:00 - 11:30 02\n";
+
+        let mut database = Database::default();
+        let mut parser = FileParser::new(ParserConfig { skip_errors: true, ..ParserConfig::default() });
+        parser.parse_into(content, &mut database).unwrap();
+
+        assert_eq!(parser.errors().len(), 1);
+        assert_eq!(parser.errors()[0].line, Some(3));
+        assert_eq!(database.watch_entries.len(), 1);
+        assert_eq!(database.watch_entries[0].start_time.date(), NaiveDate::from_ymd_opt(2022, 3, 19).unwrap());
+    }
+
+    #[test]
+    fn file_parser_skipped_rollover_keeps_the_day() {
+        let content = "19/03/2022\nA:\n23:00 - 23:30 01\n01:00 - 01:00 02\n20:00 - 20:30 03\n";
+
+        let mut database = Database::default();
+        let mut parser = FileParser::new(ParserConfig { skip_errors: true, ..ParserConfig::default() });
+        parser.parse_into(content, &mut database).unwrap();
+
+        assert_eq!(parser.errors().len(), 1);
+        assert_eq!(parser.errors()[0].line, Some(4));
+        let dates: Vec<NaiveDate> = database.watch_entries.iter().map(|entry| entry.start_time.date()).collect();
+        assert_eq!(dates, vec![NaiveDate::from_ymd_opt(2022, 3, 19).unwrap(); 2]);
+    }
+
+    #[test]
+    fn file_parser_resyncs_on_next_date() {
+        let content = "19/03/2022\nErased:\n20:00 - 20:30 01\n\
+            20/03/2022\nErased:\n25:00 - 20:30 02\n21:00 - 21:30 03\nstray\n\
+            21/03/2022\nErased:\n20:00 - 20:30 04\n";
+
+        let mut database = Database::default();
+        let mut parser = FileParser::new(ParserConfig { skip_errors: true, resync_on_date: true, ..ParserConfig::default() });
+        parser.parse_into(content, &mut database).unwrap();
+
+        let episodes: Vec<String> = database.watch_entries.iter().map(|entry| entry.episode.to_string()).collect();
+        assert_eq!(episodes, vec!["01", "04"]);
+        assert_eq!(parser.errors().len(), 1);
+        assert!(parser.errors()[0].to_string().starts_with("Line 6: Skipped lines 6-8 up to the next date: "), "{}", parser.errors()[0]);
+        assert!(parser.warnings().is_empty());
+
+        // A broken block at the end is skipped up to the last line
+        let mut parser = FileParser::new(ParserConfig { skip_errors: true, resync_on_date: true, ..ParserConfig::default() });
+        parser.parse_into("19/03/2022\nErased:\n25:00 - 20:30 01\n21:00 - 21:30 02\n", &mut Database::default()).unwrap();
+        assert!(parser.errors()[0].to_string().starts_with("Line 3: Skipped lines 3-4 "), "{}", parser.errors()[0]);
+    }
+
+    #[test]
+    fn file_parser_carries_context_across_files() {
+        let first = "30/12/2021\nErased:\n20:00 - 20:30 01\n23:50 - 00:20 02\n";
+        let second = "02/01/2022\nErased:\n20:00 - 20:30 03\n20:20 - 20:50 04\n";
+
+        let mut database = Database::default();
+        let mut parser = FileParser::new(ParserConfig::default());
+        parser.parse_files([("2021.txt", first), ("2022.txt", second)], &mut database).unwrap();
+
+        assert_eq!(database.animes, vec!["Erased".to_string()]);
+        assert_eq!(database.watch_entries.len(), 4);
+        assert_eq!(parser.warnings().len(), 1);
+        assert_eq!(parser.warnings()[0].to_string(), "2022.txt: Line 4: Entry starting at 2022-01-02 20:20:00 overlaps previous entry ending at 2022-01-02 20:30:00");
+
+        // The second file can't go back before where the first one ended, even via midnight
+        let mut parser = FileParser::new(ParserConfig::default());
+        let err = parser.parse_files([("2021.txt", first), ("2022.txt", "31/12/2021\n")], &mut Database::default()).unwrap_err();
+        assert!(err.to_string().starts_with("2022.txt: Line 1: "), "{}", err);
+
+        let dir = std::env::temp_dir().join(format!("rust-anime-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("2022.txt"), second).unwrap();
+        std::fs::write(dir.join("2021.txt"), first).unwrap();
+        let mut from_dir = Database::default();
+        let result = FileParser::new(ParserConfig::default()).parse_dir(&dir, &mut from_dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+        assert_eq!(from_dir, database);
+    }
+
+    #[test]
+    fn file_parser_detailed_report() {
+        let content = "// My log\n\
+            19/03/2022\n\
+            Erased:\n\
+            [rewatch]\n\
+            20:00 - 20:30 01\n\
+            \n\
+            stray text\n\
+            25:00 - 21:00 02\n\
+            One Pace: Wano:\n\
+            21:00 - 21:30 01\n\
+            21:20 - 21:50 02\n";
+
+        let report = FileParser::new(ParserConfig::default()).parse_detailed(content);
+
+        assert_eq!(report.dates.len(), 1);
+        assert_eq!(report.titles, vec![(3, "Erased".to_string()), (9, "One Pace: Wano".to_string())]);
+        assert_eq!(report.tags, vec![(4, "rewatch".to_string())]);
+        let entry_lines: Vec<usize> = report.entries.iter().map(|(line, _)| *line).collect();
+        assert_eq!(entry_lines, vec![5, 10, 11]);
+        assert_eq!(report.skipped_lines, vec![(7, "stray text".to_string()), (8, "25:00 - 21:00 02".to_string())]);
+
+        let diagnostics: Vec<(Option<usize>, Severity)> = report.diagnostics.iter().map(|diagnostic| (diagnostic.line, diagnostic.severity)).collect();
+        assert_eq!(diagnostics, vec![(Some(7), Severity::Warning), (Some(8), Severity::Error), (Some(11), Severity::Warning)]);
+    }
+
+    #[test]
+    fn file_parser_keeps_source_lines() {
+        let content = "19/03/2022\nErased:\n  20:00 - 20:30 01 {Gary} // pilot\n02 20:30 - 21:00\n";
+
+        let report = FileParser::new(ParserConfig { keep_source_lines: true, ..ParserConfig::default() }).parse_detailed(content);
+        let source_lines: Vec<Option<&str>> = report.entries.iter().map(|(_, entry)| entry.source_line.as_deref()).collect();
+        assert_eq!(source_lines, vec![Some("  20:00 - 20:30 01 {Gary} // pilot"), Some("02 20:30 - 21:00")]);
+
+        // Not kept by default, and never part of equality
+        let plain = FileParser::new(ParserConfig::default()).parse_detailed(content);
+        assert!(plain.entries.iter().all(|(_, entry)| entry.source_line.is_none()));
+        assert_eq!(plain.entries, report.entries);
+        let entries: std::collections::HashSet<&WatchEntry> = plain.entries.iter().chain(&report.entries).map(|(_, entry)| entry).collect();
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn file_parser_skips_bom() {
+        let content = "\u{FEFF}19/03/2022\nErased:\n20:00 - 20:30 01\n";
+
+        let mut parser = FileParser::new(ParserConfig { strict_unknown: true, ..ParserConfig::default() });
+        let database = parser.parse(content).unwrap();
+        assert_eq!(database.watch_entries[0].start_time.date(), NaiveDate::from_ymd_opt(2022, 3, 19).unwrap());
+        assert_eq!(FileParser::new(ParserConfig::default()).parse_detailed(content).dates.len(), 1);
+    }
+
+    #[test]
+    fn file_parser_episode_first_lines() {
+        let times_first = "05/03/2022\nErased:\n16:40 - 18:24 05 {Gary} [dub]\n18:30 - 18:55 1 -> 2\n  19:00 ova // extra\n";
+        let episode_first = "05/03/2022\nErased:\n05 16:40 - 18:24 {Gary} [dub]\n1 -> 2 18:30 - 18:55\n  ova 19:00 // extra\n";
+
+        let mut parser = FileParser::new(ParserConfig { strict_unknown: true, ..ParserConfig::default() });
+        let expected = parser.parse(times_first).unwrap();
+        let mut parser = FileParser::new(ParserConfig { strict_unknown: true, ..ParserConfig::default() });
+        assert_eq!(parser.parse(episode_first).unwrap(), expected);
+        assert_eq!(expected.watch_entries.len(), 3);
+
+        // Still a date, not episode 05
+        assert!(DateLineParser.parse("05/03/2022").is_ok());
+        assert_eq!(episode_first_to_standard("05/03/2022 16:40 - 18:24 05"), None);
+        // Episode-first lines are claimed, so their errors are reported as such
+        let err = FileParser::new(ParserConfig::default()).parse("05/03/2022\nErased:\n05 25:00 - 18:24\n").unwrap_err();
+        assert!(err.message.contains("Invalid start time"), "{}", err);
+    }
+
+    #[test]
+    fn file_parser_crlf_line_endings() {
+        let content = "19/03/2022\r\nErased:\r\n20:00 - 20:30 01 {Gary}\r\n\r\n// done\r\nOne Pace: Wano:\r";
+
+        let mut parser = FileParser::new(ParserConfig { strict_unknown: true, ..ParserConfig::default() });
+        let database = parser.parse(content).unwrap();
+
+        assert_eq!(database.animes, vec!["Erased".to_string(), "One Pace: Wano".to_string()]);
+        assert_eq!(database.watch_entries.len(), 1);
+        assert_eq!(database.watch_entries[0].company, Some(Company::from_str("{Gary}").unwrap()));
+    }
+
+    #[test]
+    fn watch_line_open_ended() {
+        let mut context = context_on("10/02/2022", 1);
+
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("20:00 05").unwrap();
+        assert_eq!(watch_line.start_time.time(), NaiveTime::parse_from_str("20:00", "%H:%M").unwrap());
+        assert_eq!(watch_line.end_time, None);
+        assert_eq!(watch_line.episode, Episode::from("05").unwrap());
+
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("21:00 - 06 {Gary}").unwrap();
+        assert_eq!(watch_line.start_time.time(), NaiveTime::parse_from_str("21:00", "%H:%M").unwrap());
+        assert_eq!(watch_line.end_time, None);
+        assert_eq!(watch_line.episode, Episode::from("06").unwrap());
+        assert_eq!(watch_line.company, Some(Company::from_str("{Gary}").unwrap()));
+
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("22:00 - 23:00 07").unwrap();
+        assert_eq!(watch_line.end_time.unwrap().time(), NaiveTime::parse_from_str("23:00", "%H:%M").unwrap());
+        assert_eq!(watch_line.episode, Episode::from("07").unwrap());
+    }
+
+    #[test]
+    fn watch_line_zero_duration() {
+        let mut context = context_on("10/02/2022", 1);
+
+        let rejected = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("22:00 - 22:00 05");
+        assert!(rejected.unwrap_err().message.contains("zero duration"));
+
+        let config = ParserConfig { allow_zero_duration: true, ..ParserConfig::default() };
+        let marker = WatchLineParser{context: &mut context, config: &config}.parse("22:00 - 22:00 05").unwrap();
+        assert_eq!(marker.duration(), Some(Duration::zero()));
+        assert_eq!(marker.start_time.date(), NaiveDate::parse_from_str("10/02/2022", "%d/%m/%Y").unwrap());
+    }
+
+    #[test]
+    fn watch_line_session_too_long() {
+        let mut context = context_on("10/02/2022", 1);
+
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("23:40 - 00:20 12");
+        assert!(watch_line.is_ok());
+
+        // Day offsets count towards the session: 25 hours is over the default maximum
+        let before = context.clone();
+        let watch_line = WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("01:00 - 02:00+1 13");
+        assert_eq!(watch_line.unwrap_err().message, "Session of 25h00m reaches the 24h maximum (typo in the times?)");
+        assert_eq!(context, before, "Rejected line shouldn't touch the context");
+        assert!(WatchLineParser{context: &mut context, config: &ParserConfig::default()}.parse("01:00 - 01:00+1 13").is_err());
+
+        // Clock-only lines stay under a day, so catching a 23-hour rollover takes a lower maximum
+        let config = ParserConfig { max_session_hours: 6, ..ParserConfig::default() };
+        let watch_line = WatchLineParser{context: &mut context, config: &config}.parse("03:00 - 02:00 13");
+        assert_eq!(watch_line.unwrap_err().message, "Session of 23h00m reaches the 6h maximum (typo in the times?)");
+        assert_eq!(context, before, "Rejected line shouldn't touch the context");
+
+        let watch_line = WatchLineParser{context: &mut context, config: &config}.parse("01:00 - 07:30 13");
+        assert!(watch_line.is_err());
+    }
+
+    #[test]
+    fn dyn_line_parsers_classify_lines() {
+        fn classify(parsers: &mut [Box<dyn DynLineParser>], context: &mut ParsingContext, line: &str) -> Option<ParsedLine> {
+            parsers.iter_mut().find_map(|parser| parser.parse_line(line, context, &ParserConfig::default()).ok())
+        }
+
+        let mut parsers: Vec<Box<dyn DynLineParser>> = default_line_parsers();
+        let mut context = ParsingContext::new();
+
+        let date = NaiveDate::parse_from_str("19/03/2022", "%d/%m/%Y").unwrap();
+        assert_eq!(classify(&mut parsers, &mut context, "19/03/2022"), Some(ParsedLine::Date(date)));
+        assert_eq!(classify(&mut parsers, &mut context, "One Pace: Wano:"), Some(ParsedLine::Title("One Pace: Wano".to_string())));
+        assert_eq!(classify(&mut parsers, &mut context, "[marathon]"), Some(ParsedLine::Tag("marathon".to_string())));
+        assert_eq!(classify(&mut parsers, &mut context, "stray text"), None);
+
+        context.notify_new_current_date(date).unwrap();
+        context.notify_new_current_anime(0).unwrap();
+        match classify(&mut parsers, &mut context, "22:11 - 22:35 01") {
+            Some(ParsedLine::Watch(entry)) => assert_eq!(entry.episode, Episode::from("01").unwrap()),
+            other => panic!("Expected a watch entry, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Offset, TimeZone};
+use chrono_tz::Tz;
+
+use crate::database::simple_database::SimpleDatabase;
+use crate::database::Anime;
+use crate::elements::WatchEntry;
+
+/// An output format for the whole library. Entry ratings are not part of any format and are dropped.
+pub trait Report {
+    /// Name used to pick the report, e.g. `export --format json`
+    fn format(&self) -> &'static str;
+    fn render(&self, db: &SimpleDatabase) -> String;
+}
+
+/// Built-in reports, looked up by `format`
+pub fn builtin_reports() -> Vec<Box<dyn Report>> {
+    vec![Box::new(JsonReport), Box::new(NdjsonReport), Box::new(CsvReport), Box::new(IcalReport), Box::new(LogReport)]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Ndjson,
+    Csv,
+    Ical,
+    Log,
+}
+
+impl Format {
+    pub fn report(self) -> Box<dyn Report> {
+        match self {
+            Self::Json => Box::new(JsonReport),
+            Self::Ndjson => Box::new(NdjsonReport),
+            Self::Csv => Box::new(CsvReport),
+            Self::Ical => Box::new(IcalReport),
+            Self::Log => Box::new(LogReport),
+        }
+    }
+}
+
+pub fn report_for_format(format: &str) -> Option<Box<dyn Report>> {
+    builtin_reports().into_iter().find(|report| report.format() == format)
+}
+
+/// Every entry with its anime, ordered by anime id and then start time
+fn sorted_entries(db: &SimpleDatabase) -> Vec<(&Anime, &WatchEntry)> {
+    let mut animes: Vec<&Anime> = db.animes().collect();
+    animes.sort_by_key(|anime| anime.id());
+
+    animes.into_iter()
+        .flat_map(|anime| anime.entries_chronological().into_iter().map(move |entry| (anime, entry)))
+        .collect()
+}
+
+/// `time` read as a local time in `timezone`. A time skipped by a DST change can't be
+/// read as is, so it takes the offset in force at the same UTC time instead.
+fn localize(time: NaiveDateTime, timezone: Tz) -> DateTime<Tz> {
+    timezone.from_local_datetime(&time).earliest().unwrap_or_else(|| {
+        let offset = timezone.offset_from_utc_datetime(&time).fix().local_minus_utc();
+        timezone.from_utc_datetime(&(time - chrono::Duration::seconds(offset.into())))
+    })
+}
+
+/// ISO 8601, with the offset (RFC 3339) when the times were logged in a known timezone
+fn format_time(time: NaiveDateTime, timezone: Option<Tz>) -> String {
+    match timezone {
+        Some(timezone) => localize(time, timezone).format("%Y-%m-%dT%H:%M:%S%:z").to_string(),
+        None => time.format("%Y-%m-%dT%H:%M:%S").to_string(),
+    }
+}
+
+pub struct JsonReport;
+
+impl JsonReport {
+    fn string(value: &str) -> String {
+        let mut escaped = String::with_capacity(value.len() + 2);
+        escaped.push('"');
+        for c in value.chars() {
+            match c {
+                '"' => escaped.push_str("\\\""),
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\n"),
+                c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+                c => escaped.push(c),
+            }
+        }
+        escaped.push('"');
+        escaped
+    }
+
+    fn optional_string(value: Option<String>) -> String {
+        value.map_or_else(|| "null".to_string(), |value| Self::string(&value))
+    }
+
+    fn object(anime: &Anime, entry: &WatchEntry, timezone: Option<Tz>) -> String {
+        format!(
+            "{{\"anime\":{},\"episode\":{},\"start\":{},\"end\":{},\"company\":{}}}",
+            Self::string(anime.name()),
+            Self::string(&entry.episode.to_string()),
+            Self::string(&format_time(entry.start_time, timezone)),
+            Self::optional_string(entry.end_time.map(|end_time| format_time(end_time, timezone))),
+            Self::optional_string(entry.company.as_ref().map(ToString::to_string)),
+        )
+    }
+}
+
+impl Report for JsonReport {
+    fn format(&self) -> &'static str {
+        "json"
+    }
+
+    fn render(&self, db: &SimpleDatabase) -> String {
+        let entries: Vec<String> = sorted_entries(db).into_iter()
+            .map(|(anime, entry)| Self::object(anime, entry, db.timezone()))
+            .collect();
+
+        format!("[{}]\n", entries.join(","))
+    }
+}
+
+/// JSON Lines: the objects of `JsonReport`, one per line
+pub struct NdjsonReport;
+
+impl Report for NdjsonReport {
+    fn format(&self) -> &'static str {
+        "ndjson"
+    }
+
+    fn render(&self, db: &SimpleDatabase) -> String {
+        sorted_entries(db).into_iter()
+            .map(|(anime, entry)| JsonReport::object(anime, entry, db.timezone()) + "\n")
+            .collect()
+    }
+}
+
+pub struct CsvReport;
+
+impl CsvReport {
+    fn field(value: &str) -> String {
+        if value.contains([',', '"', '\n']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+}
+
+impl Report for CsvReport {
+    fn format(&self) -> &'static str {
+        "csv"
+    }
+
+    fn render(&self, db: &SimpleDatabase) -> String {
+        let mut csv = String::from("anime,episode,start,end,company\n");
+        for (anime, entry) in sorted_entries(db) {
+            let fields = [
+                anime.name().to_string(),
+                entry.episode.to_string(),
+                format_time(entry.start_time, db.timezone()),
+                entry.end_time.map(|end_time| format_time(end_time, db.timezone())).unwrap_or_default(),
+                entry.company.as_ref().map(ToString::to_string).unwrap_or_default(),
+            ];
+            csv.push_str(&fields.iter().map(|field| Self::field(field)).collect::<Vec<_>>().join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// One event per entry; open-ended entries only get a start.
+/// Times are floating (local) unless the database has a timezone, then they are in UTC.
+pub struct IcalReport;
+
+impl IcalReport {
+    fn text(value: &str) -> String {
+        value.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+    }
+
+    fn time(time: NaiveDateTime, timezone: Option<Tz>) -> String {
+        match timezone {
+            Some(timezone) => localize(time, timezone).naive_utc().format("%Y%m%dT%H%M%SZ").to_string(),
+            None => time.format("%Y%m%dT%H%M%S").to_string(),
+        }
+    }
+}
+
+impl Report for IcalReport {
+    fn format(&self) -> &'static str {
+        "ical"
+    }
+
+    fn render(&self, db: &SimpleDatabase) -> String {
+        let mut lines = vec!["BEGIN:VCALENDAR".to_string(), "VERSION:2.0".to_string(), "PRODID:-//rust-anime//EN".to_string()];
+        for (anime, entry) in sorted_entries(db) {
+            lines.push("BEGIN:VEVENT".to_string());
+            // The UID stays the same whatever the timezone
+            lines.push(format!("UID:{}-{}@rust-anime", anime.id(), Self::time(entry.start_time, None)));
+            lines.push(format!("DTSTART:{}", Self::time(entry.start_time, db.timezone())));
+            if let Some(end_time) = entry.end_time {
+                lines.push(format!("DTEND:{}", Self::time(end_time, db.timezone())));
+            }
+            lines.push(format!("SUMMARY:{}", Self::text(&format!("{} - {}", anime.name(), entry.episode))));
+            lines.push("END:VEVENT".to_string());
+        }
+        lines.push("END:VCALENDAR".to_string());
+
+        // iCalendar lines are CRLF-terminated
+        lines.into_iter().map(|line| line + "\r\n").collect()
+    }
+}
+
+/// The watch log format itself, readable back by `FileParser`
+pub struct LogReport;
+
+impl Report for LogReport {
+    fn format(&self) -> &'static str {
+        "log"
+    }
+
+    fn render(&self, db: &SimpleDatabase) -> String {
+        let mut entries = sorted_entries(db);
+        entries.sort_by_key(|(anime, entry)| (entry.start_time, anime.id()));
+
+        let mut lines: Vec<String> = vec![];
+        // What the parser will believe after each line, to only write headers when they change
+        let mut current_date: Option<NaiveDate> = None;
+        let mut current_anime = None;
+        let mut current_tag: Option<&String> = None;
+        for (anime, entry) in entries {
+            let date = entry.start_time.date();
+            if current_date.is_none_or(|current_date| current_date < date) {
+                if !lines.is_empty() {
+                    lines.push(String::new());
+                }
+                lines.push(date.format("%d/%m/%Y").to_string());
+                current_date = Some(date);
+                current_anime = None;
+            }
+
+            // A tag is only cleared by a new title
+            if current_anime != Some(anime.id()) || (current_tag.is_some() && entry.session_tag.is_none()) {
+                lines.push(format!("{}:", anime.name()));
+                current_anime = Some(anime.id());
+                current_tag = None;
+            }
+            if let Some(tag) = &entry.session_tag {
+                if current_tag != Some(tag) {
+                    lines.push(format!("[{}]", tag));
+                    current_tag = Some(tag);
+                }
+            }
+
+            let mut line = entry.start_time.format("%H:%M").to_string();
+            if let Some(end_time) = entry.end_time {
+                line += &end_time.format(" - %H:%M").to_string();
+                // Spans the parser wouldn't guess from the clock alone get an explicit day offset
+                let days = (end_time.date() - entry.start_time.date()).num_days();
+                if days > 1 || (days == 1 && end_time.time() >= entry.start_time.time()) {
+                    line += &format!("+{}", days);
+                }
+                // The parser moves on to the next day after an entry crossing midnight
+                current_date = current_date.max(Some(end_time.date()));
+            }
+            line += &format!(" {}", entry.episode);
+            if let Some(company) = &entry.company {
+                line += &format!(" {}", company);
+            }
+            for tag in &entry.tags {
+                line += &format!(" [{}]", tag);
+            }
+            lines.push(line);
+        }
+
+        lines.into_iter().map(|line| line + "\n").collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::{FileParser, ParserConfig};
+
+    fn fixture_db() -> SimpleDatabase {
+        let content = "19/03/2022\nShow, The:\n20:00 - 20:30 01 {Gary, Amim}\n20:30 - 21:00 02\nOther:\n21:00 03\n";
+        let mut db = SimpleDatabase::new();
+        FileParser::new(ParserConfig::default()).parse_into(content, &mut db).unwrap();
+        db
+    }
+
+    #[test]
+    fn every_builtin_report_renders_all_entries() {
+        let db = fixture_db();
+
+        for report in builtin_reports() {
+            let output = report.render(&db);
+            assert!(output.contains("Show"), "{} report: {}", report.format(), output);
+            assert!(output.contains("Other"), "{} report: {}", report.format(), output);
+            assert!(output.contains("20220319T2000") || output.contains("2022-03-19T20:00:00") || output.contains("19/03/2022\nShow, The:\n20:00"), "{} report: {}", report.format(), output);
+            assert_eq!(report_for_format(report.format()).unwrap().render(&db), output);
+        }
+        assert!(report_for_format("xml").is_none());
+    }
+
+    #[test]
+    fn log_report_parses_back() {
+        let content = "19/03/2022\nErased:\n[rewatch]\n23:30 - 00:10 01 {Gary,Amim}\n00:15 - 00:40 02\nOne Pace: Wano:\n01:00 07.5 [dub] [bluray]\n\n21/03/2022\nErased:\n20:00 - 20:20 [03,04]\n";
+        let mut db = SimpleDatabase::new();
+        FileParser::new(ParserConfig::default()).parse_into(content, &mut db).unwrap();
+
+        let log = LogReport.render(&db);
+        assert_eq!(log, content);
+
+        let mut parsed_back = SimpleDatabase::new();
+        FileParser::new(ParserConfig { strict_unknown: true, ..ParserConfig::default() }).parse_into(&log, &mut parsed_back).unwrap();
+        assert_eq!(JsonReport.render(&parsed_back), JsonReport.render(&db));
+    }
+
+    #[test]
+    fn log_report_writes_day_offsets() {
+        let content = "19/03/2022\nErased:\n23:00 - 07:00 01\n\n21/03/2022\nErased:\n20:00 - 21:00+1 02\n22:00 - 20:00+2 03\n";
+        let mut db = SimpleDatabase::new();
+        FileParser::new(ParserConfig { max_session_hours: 48, ..ParserConfig::default() }).parse_into(content, &mut db).unwrap();
+
+        assert_eq!(LogReport.render(&db), content);
+    }
+
+    #[test]
+    fn reports_attach_the_timezone() {
+        let content = "19/03/2022\nErased:\n20:00 - 20:30 01\n";
+        let render = |timezone: Option<Tz>, report: &dyn Report| {
+            let mut db = SimpleDatabase::new();
+            FileParser::new(ParserConfig { timezone, ..ParserConfig::default() }).parse_into(content, &mut db).unwrap();
+            report.render(&db)
+        };
+
+        assert!(render(None, &JsonReport).contains("\"start\":\"2022-03-19T20:00:00\",\"end\":\"2022-03-19T20:30:00\""));
+        assert!(render(Some(chrono_tz::America::Sao_Paulo), &JsonReport).contains("\"start\":\"2022-03-19T20:00:00-03:00\",\"end\":\"2022-03-19T20:30:00-03:00\""));
+        assert!(render(Some(chrono_tz::Asia::Tokyo), &JsonReport).contains("\"start\":\"2022-03-19T20:00:00+09:00\",\"end\":\"2022-03-19T20:30:00+09:00\""));
+
+        assert!(render(None, &IcalReport).contains("DTSTART:20220319T200000\r\nDTEND:20220319T203000\r\n"));
+        let sao_paulo = render(Some(chrono_tz::America::Sao_Paulo), &IcalReport);
+        assert!(sao_paulo.contains("DTSTART:20220319T230000Z\r\nDTEND:20220319T233000Z\r\n"), "{}", sao_paulo);
+        let tokyo = render(Some(chrono_tz::Asia::Tokyo), &IcalReport);
+        assert!(tokyo.contains("DTSTART:20220319T110000Z\r\nDTEND:20220319T113000Z\r\n"), "{}", tokyo);
+        assert!(tokyo.contains("UID:0-20220319T200000@rust-anime\r\n"));
+    }
+
+    #[test]
+    fn times_skipped_by_dst_still_export() {
+        // Clocks in New York jumped from 02:00 to 03:00 on 13/03/2022
+        let time = NaiveDateTime::parse_from_str("13/03/2022 02:30", "%d/%m/%Y %H:%M").unwrap();
+        assert_eq!(format_time(time, Some(chrono_tz::America::New_York)), "2022-03-13T03:30:00-04:00");
+    }
+
+    #[test]
+    fn reports_escape_their_fields() {
+        let db = fixture_db();
+
+        let csv = CsvReport.render(&db);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "anime,episode,start,end,company");
+        assert_eq!(lines[1], "\"Show, The\",01,2022-03-19T20:00:00,2022-03-19T20:30:00,\"{Gary,Amim}\"");
+        assert_eq!(lines[3], "Other,03,2022-03-19T21:00:00,,");
+
+        let json = JsonReport.render(&db);
+        assert!(json.starts_with("[{\"anime\":\"Show, The\",\"episode\":\"01\",\"start\":\"2022-03-19T20:00:00\""));
+        assert!(json.contains("\"end\":null,\"company\":null}]"));
+
+        let ical = IcalReport.render(&db);
+        assert!(ical.contains("SUMMARY:Show\\, The - 01\r\n"));
+        assert_eq!(ical.matches("BEGIN:VEVENT").count(), 3);
+        assert_eq!(ical.matches("DTEND:").count(), 2);
+    }
+}
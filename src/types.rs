@@ -1,3 +1,158 @@
-pub type ParseDiagnostic = String;
+use std::fmt;
+
+use chrono::{Days, Months, NaiveDate};
+
 pub type AnimeID = usize; 
 pub type Diagnostic = String;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Suspicious but parsed, e.g. overlapping entries
+    Warning,
+    /// The line could not be used
+    Error,
+}
+
+/// Problem found while parsing, tagged with its line (and file) once `FileParser` knows it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDiagnostic {
+    pub severity: Severity,
+    /// Name of the file, when parsing several of them
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl ParseDiagnostic {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, file: None, line: None, message: message.into() }
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, file: None, line: None, message: message.into() }
+    }
+
+    pub fn at_line(self, line: usize) -> Self {
+        Self { line: Some(line), ..self }
+    }
+
+    pub fn in_file(self, file: impl Into<String>) -> Self {
+        Self { file: Some(file.into()), ..self }
+    }
+}
+
+/// Plain messages from the element parsers are errors
+impl From<String> for ParseDiagnostic {
+    fn from(message: String) -> Self {
+        Self::error(message)
+    }
+}
+
+impl fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}: ", file)?;
+        }
+        match self.line {
+            Some(line) => write!(f, "Line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ParseDiagnostic {}
+
+/// Canonical form of an anime title used for matching (not for display):
+/// lowercase, one space after each colon, whitespace collapsed and trailing colons trimmed
+pub fn normalize_title(title: &str) -> String {
+    title
+        .to_lowercase()
+        .replace(':', ": ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches([':', ' '])
+        .to_string()
+}
+
+/// Levenshtein distance between `a` and `b`, counted in chars
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// First day of a relative span like `7d`, `2w`, `1mo` or `1y`, counted back from `reference`
+pub fn span_start(span: &str, reference: NaiveDate) -> Result<NaiveDate, Diagnostic> {
+    let span = span.trim();
+    let invalid = || format!("Invalid time span \"{}\" (expected a count followed by d, w, mo or y, e.g. 7d)", span);
+
+    let unit_start = span.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (count, unit) = span.split_at(unit_start);
+    let count: u32 = count.parse().map_err(|_| invalid())?;
+
+    let start = match unit {
+        "d" => reference.checked_sub_days(Days::new(count.into())),
+        "w" => reference.checked_sub_days(Days::new(u64::from(count) * 7)),
+        "mo" => reference.checked_sub_months(Months::new(count)),
+        "y" => count.checked_mul(12).and_then(|months| reference.checked_sub_months(Months::new(months))),
+        _ => return Err(invalid()),
+    };
+    start.ok_or_else(|| format!("Time span \"{}\" goes back too far", span))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_title_ignores_case_and_spacing() {
+        assert_eq!(normalize_title("Re:zero kara Hajimeru isekai Seikatsu"), "re: zero kara hajimeru isekai seikatsu");
+        assert_eq!(normalize_title("Re: Zero  Kara Hajimeru Isekai Seikatsu:"), "re: zero kara hajimeru isekai seikatsu");
+        assert_eq!(normalize_title("  One Pace: Wano  "), "one pace: wano");
+    }
+
+    #[test]
+    fn edit_distance_counts_char_edits() {
+        assert_eq!(edit_distance("frieren", "frieren"), 0);
+        assert_eq!(edit_distance("frieren", "freiren"), 2);
+        assert_eq!(edit_distance("erased", "erase"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("shingeki", "shingeki no kyojin"), 10);
+    }
+
+    #[test]
+    fn span_start_units() {
+        let reference = NaiveDate::from_ymd_opt(2022, 3, 31).unwrap();
+        assert_eq!(span_start("7d", reference), Ok(NaiveDate::from_ymd_opt(2022, 3, 24).unwrap()));
+        assert_eq!(span_start("2w", reference), Ok(NaiveDate::from_ymd_opt(2022, 3, 17).unwrap()));
+        assert_eq!(span_start("1mo", reference), Ok(NaiveDate::from_ymd_opt(2022, 2, 28).unwrap()));
+        assert_eq!(span_start("1y", reference), Ok(NaiveDate::from_ymd_opt(2021, 3, 31).unwrap()));
+        assert_eq!(span_start("0d", reference), Ok(reference));
+
+        for span in ["7", "d", "7x", "-7d", "7 d", "1m", ""] {
+            assert!(span_start(span, reference).unwrap_err().contains("Invalid time span"), "{:?}", span);
+        }
+        assert!(span_start("4000000000y", reference).is_err());
+    }
+
+    #[test]
+    fn parse_diagnostic_display() {
+        let diagnostic = ParseDiagnostic::warning("Entry overlaps");
+        assert_eq!(diagnostic.to_string(), "Entry overlaps");
+        assert_eq!(diagnostic.at_line(4).to_string(), "Line 4: Entry overlaps");
+
+        let diagnostic: ParseDiagnostic = "Bad date".to_string().into();
+        assert_eq!(diagnostic.severity, Severity::Error);
+    }
+}
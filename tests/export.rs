@@ -0,0 +1,25 @@
+use std::process::Command;
+
+fn run_export(format: &str, fixture: &str) -> (Option<i32>, String) {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), fixture);
+    let output = Command::new(env!("CARGO_BIN_EXE_rust-anime"))
+        .args(["export", "--format", format, &path])
+        .output()
+        .unwrap();
+
+    (output.status.code(), String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn export_dispatches_on_format() {
+    let (code, stdout) = run_export("csv", "valid.txt");
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout.lines().count(), 7);
+
+    let (code, stdout) = run_export("ical", "valid.txt");
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout.matches("BEGIN:VEVENT").count(), 6);
+
+    let (code, _) = run_export("xml", "valid.txt");
+    assert_eq!(code, Some(2));
+}
@@ -0,0 +1,16 @@
+mod support;
+
+use rust_anime::database::simple_database::SimpleDatabase;
+use rust_anime::parsing::{FileParser, ParserConfig};
+
+#[test]
+fn generated_log_is_valid() {
+    let log = support::generate_log(10_000);
+    assert!(log.lines().count() >= 10_000);
+
+    let mut db = SimpleDatabase::new();
+    let mut parser = FileParser::new(ParserConfig { strict_unknown: true, ..ParserConfig::default() });
+    parser.parse_into(&log, &mut db).unwrap();
+    assert!(parser.warnings().is_empty(), "{:?}", parser.warnings().first());
+    assert!(db.cached_entry_count() > 5_000);
+}
@@ -0,0 +1,37 @@
+use std::process::Command;
+
+use rust_anime::database::simple_database::SimpleDatabase;
+use rust_anime::parsing::{FileParser, ParserConfig};
+
+fn run_log(args: &[&str], path: &str) -> bool {
+    Command::new(env!("CARGO_BIN_EXE_rust-anime"))
+        .arg("log")
+        .args(args)
+        .arg(path)
+        .output()
+        .unwrap()
+        .status
+        .success()
+}
+
+#[test]
+fn log_appends_under_todays_date() {
+    let path = std::env::temp_dir().join(format!("rust-anime-log-{}.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::write(path, "01/01/2020\nErased:\n20:00 - 20:30 01\n").unwrap();
+
+    assert!(run_log(&["--anime", "Erased", "--start", "21:00", "--end", "21:25", "--episode", "2", "--with", "Gary, Amim"], path));
+    assert!(run_log(&["--anime", "Erased", "--start", "21:30", "--end", "21:55", "--episode", "03"], path));
+    // Rejected by the parser, so nothing is written
+    assert!(!run_log(&["--anime", "Erased", "--start", "22:00", "--end", "22:25", "--episode", "1 -> x"], path));
+
+    let content = std::fs::read_to_string(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    let today = chrono::Local::now().date_naive().format("%d/%m/%Y");
+    assert_eq!(content, format!("01/01/2020\nErased:\n20:00 - 20:30 01\n\n{}\nErased:\n21:00 - 21:25 02 {{Gary, Amim}}\nErased:\n21:30 - 21:55 03\n", today));
+
+    let mut db = SimpleDatabase::new();
+    FileParser::new(ParserConfig { strict_unknown: true, ..ParserConfig::default() }).parse_into(&content, &mut db).unwrap();
+    assert_eq!(db.search("Erased")[0].watch_entries().count(), 3);
+}
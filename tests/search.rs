@@ -0,0 +1,19 @@
+use std::process::Command;
+
+fn run_search(query: &str) -> String {
+    let path = format!("{}/tests/fixtures/valid.txt", env!("CARGO_MANIFEST_DIR"));
+    let output = Command::new(env!("CARGO_BIN_EXE_rust-anime"))
+        .args(["search", query, &path])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn search_lists_ranked_matches() {
+    assert_eq!(run_search("one pace: wano"), "One Pace: Wano\n");
+    assert_eq!(run_search("One Pace"), "One Pace: Reverie\nOne Pace: Wano\n");
+    assert_eq!(run_search("Bebop"), "");
+}
@@ -0,0 +1,55 @@
+use chrono::{Duration, NaiveDate, NaiveTime};
+
+const ANIMES: [&str; 8] = [
+    "Erased",
+    "One Pace: Wano",
+    "Evangelion: 1.0 You Are (Not) Alone",
+    "Cowboy Bebop",
+    "Made in Abyss",
+    "Steins;Gate",
+    "Mob Psycho 100",
+    "Frieren",
+];
+
+/// Deterministic, valid watch log of at least `min_lines` lines, mixing every line kind
+pub fn generate_log(min_lines: usize) -> String {
+    let mut lines: Vec<String> = vec![];
+    let mut episodes = [0; ANIMES.len()];
+    let mut seed: u32 = 42;
+    let mut next = move |bound: u32| {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        (seed >> 16) % bound
+    };
+
+    let mut date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+    while lines.len() < min_lines {
+        lines.push(format!("{} // day {}", date.format("%d/%m/%Y"), lines.len()));
+
+        let mut time = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+        for _ in 0..4 {
+            let anime = next(ANIMES.len() as u32) as usize;
+            lines.push(format!("{}:", ANIMES[anime]));
+            if next(4) == 0 {
+                lines.push("[rewatch]".to_string());
+            }
+
+            for _ in 0..1 + next(4) {
+                episodes[anime] += 1;
+                let end = time + Duration::minutes(20 + next(10) as i64);
+                let company = match next(3) {
+                    0 => " {Gary, Amim}",
+                    1 => " {Lucas Romero, +1}",
+                    _ => "",
+                };
+                lines.push(format!("{} - {} {:02}{}", time.format("%H:%M"), end.format("%H:%M"), episodes[anime], company));
+                time = end + Duration::minutes(next(5) as i64);
+            }
+        }
+
+        lines.push("// end of day".to_string());
+        lines.push(String::new());
+        date = date.succ_opt().unwrap();
+    }
+
+    lines.join("\n")
+}
@@ -0,0 +1,62 @@
+use std::process::Command;
+
+fn run_validate(fixture: &str) -> (bool, String) {
+    let (success, stdout, _) = run_validate_with_stderr(fixture);
+    (success, stdout)
+}
+
+fn run_validate_with_stderr(fixture: &str) -> (bool, String, String) {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), fixture);
+    let output = Command::new(env!("CARGO_BIN_EXE_rust-anime"))
+        .args(["validate", &path])
+        .output()
+        .unwrap();
+
+    (output.status.success(), String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+}
+
+#[test]
+fn validate_valid_file() {
+    let (success, stdout) = run_validate("valid.txt");
+    assert!(success);
+    assert_eq!(stdout.trim(), "OK, 6 entries, 3 animes");
+}
+
+#[test]
+fn validate_reports_every_issue() {
+    let (success, stdout) = run_validate("issues.txt");
+    assert!(!success);
+
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 4, "Unexpected output: {}", stdout);
+    assert!(lines[0].starts_with("Line 4:") && lines[0].contains("overlaps"));
+    assert!(lines[1].starts_with("Line 5:") && lines[1].contains("Episode 1 comes after episode 2"));
+    assert!(lines[2].starts_with("Line 6:") && lines[2].contains("Unrecognized line"));
+    assert!(lines[3].starts_with("Line 7:") && lines[3].contains("earlier or equal"));
+}
+
+#[test]
+fn validate_counts_each_severity() {
+    let (_, _, stderr) = run_validate_with_stderr("issues.txt");
+    assert_eq!(stderr.trim(), "2 errors, 2 warnings");
+
+    let (_, _, stderr) = run_validate_with_stderr("duplicates.txt");
+    assert_eq!(stderr.trim(), "0 errors, 1 warnings");
+}
+
+#[test]
+fn validate_reports_duplicate_entries() {
+    let (success, stdout) = run_validate("duplicates.txt");
+    assert!(!success);
+
+    let lines: Vec<&str> = stdout.lines().collect();
+    // Reported once, not also as an overlap of the line it repeats
+    assert_eq!(lines, vec!["Duplicate entry: episode 01 at 2022-03-19 20:00:00"]);
+}
+
+#[test]
+fn validate_warns_on_short_sessions() {
+    let (success, stdout) = run_validate("short_sessions.txt");
+    assert!(!success);
+    assert_eq!(stdout.trim(), "Line 3: Session for episode 05 lasts 1 min, less than 3");
+}